@@ -0,0 +1,400 @@
+//! 参数数组批量执行
+//!
+//! 同一条语句的多组参数按列写进列式缓冲区（[`ColumnarBulkInserter`]），每次往返发送一批
+//! （`batch_size` 组），适合大批量的 INSERT / UPDATE。
+//!
+//! 每一列的缓冲区类型由该列第一个非 NULL 的值决定，全部为 NULL 的列通过 `SQLDescribeParam`
+//! 取参数类型，驱动不支持时按 VARCHAR 绑定。同一列中类型不兼容的值会返回错误，不做隐式转换。
+
+use std::borrow::Cow;
+
+use odbc_api::buffers::{AnyBuffer, AnySliceMut, BufferDesc};
+use odbc_api::handles::{AsStatementRef, Statement};
+use odbc_api::parameter::WithDataType;
+use odbc_api::sys::{Date, Time, Timestamp, SQLRowCount, SqlReturn};
+use odbc_api::{Bit, ColumnarBulkInserter, DataType, InOrder, Prepared};
+use rbdc::Error;
+use rbs::Value;
+
+use crate::common::error::odbc_error;
+use crate::encode::{date_value, datetime_value, decimal_text, time_value, timestamp_value};
+
+/// 批量执行的结果
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct BatchResult {
+    /// 所有批次影响的行数之和
+    pub rows_affected: u64,
+    /// 每一批影响的行数，驱动不提供时为 0
+    pub batches: Vec<u64>,
+}
+
+/// 参数列的缓冲区类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnKind {
+    Bit,
+    /// 所有整数都按 BIGINT 绑定
+    BigInt,
+    /// 所有浮点数都按 DOUBLE 绑定
+    Double,
+    Date,
+    Time,
+    Timestamp,
+    /// 字符串、DECIMAL、JSON 等按文本绑定，缓冲区长度随每批的最大值增长
+    Text,
+    Binary,
+}
+
+impl ColumnKind {
+    /// 值对应的类型，NULL 返回 `None`
+    pub fn of(value: &Value) -> Option<Self> {
+        let kind = match value {
+            Value::Null => return None,
+            Value::Bool(_) => ColumnKind::Bit,
+            Value::I32(_) | Value::I64(_) | Value::U32(_) | Value::U64(_) => ColumnKind::BigInt,
+            Value::F32(_) | Value::F64(_) => ColumnKind::Double,
+            Value::Binary(_) => ColumnKind::Binary,
+            Value::Ext("Date", _) => ColumnKind::Date,
+            Value::Ext("Time", _) => ColumnKind::Time,
+            Value::Ext("DateTime" | "Timestamp", _) => ColumnKind::Timestamp,
+            Value::Ext("Json" | "Uuid", v) if matches!(**v, Value::Binary(_)) => ColumnKind::Binary,
+            _ => ColumnKind::Text,
+        };
+        Some(kind)
+    }
+
+    /// 按第 `column` 列第一个非 NULL 的值推断类型，全部为 NULL 时返回 `None`
+    ///
+    /// ```
+    /// use rbdc_dameng::batch::ColumnKind;
+    /// use rbs::Value;
+    ///
+    /// let rows = vec![
+    ///     vec![Value::I32(1), Value::Null],
+    ///     vec![Value::I32(2), Value::String("b".to_string())],
+    /// ];
+    /// assert_eq!(ColumnKind::infer(&rows, 0), Some(ColumnKind::BigInt));
+    /// assert_eq!(ColumnKind::infer(&rows, 1), Some(ColumnKind::Text));
+    /// ```
+    pub fn infer(rows: &[Vec<Value>], column: usize) -> Option<Self> {
+        rows.iter().find_map(|row| row.get(column).and_then(Self::of))
+    }
+
+    /// 缓冲区描述，文本 / 二进制的初始长度为 1，写入每批之前按需增长
+    pub fn buffer_desc(self) -> BufferDesc {
+        match self {
+            ColumnKind::Bit => BufferDesc::Bit { nullable: true },
+            ColumnKind::BigInt => BufferDesc::I64 { nullable: true },
+            ColumnKind::Double => BufferDesc::F64 { nullable: true },
+            ColumnKind::Date => BufferDesc::Date { nullable: true },
+            ColumnKind::Time => BufferDesc::Time { nullable: true },
+            ColumnKind::Timestamp => BufferDesc::Timestamp { nullable: true },
+            ColumnKind::Text => BufferDesc::Text { max_str_len: 1 },
+            ColumnKind::Binary => BufferDesc::Binary { length: 1 },
+        }
+    }
+
+    /// 绑定时声明的 SQL 类型，与单条执行时的编码（见 [`crate::encode`]）一致
+    fn data_type(self, buffer: &AnyBuffer) -> DataType {
+        match self {
+            ColumnKind::Time => DataType::Time { precision: 0 },
+            ColumnKind::Timestamp => DataType::Timestamp { precision: 6 },
+            _ => odbc_api::handles::HasDataType::data_type(buffer),
+        }
+    }
+}
+
+/// 一列参数：缓冲区类型和绑定时声明的 SQL 类型（全部为 NULL 时来自 `SQLDescribeParam`）
+struct BatchColumn {
+    kind: ColumnKind,
+    described: Option<DataType>,
+}
+
+impl BatchColumn {
+    fn buffer(&self, capacity: usize, column: usize) -> Result<WithDataType<AnyBuffer>, Error> {
+        let buffer = AnyBuffer::try_from_desc(capacity, self.kind.buffer_desc())
+            .map_err(|e| odbc_error(e.add_context(column as u16)))?;
+        let data_type = self.described.unwrap_or_else(|| self.kind.data_type(&buffer));
+        Ok(WithDataType {
+            value: buffer,
+            data_type,
+        })
+    }
+
+    /// 把一批中第 `column` 列的值写进缓冲区，`first_row` 是这一批第一行的行号，用于错误信息
+    fn fill(&self, slice: AnySliceMut<'_>, rows: &[Vec<Value>], column: usize, first_row: usize) -> Result<(), Error> {
+        let values = rows.iter().map(|row| &row[column]);
+        let mismatch = |index: usize, value: &Value| {
+            Error::from(format!(
+                "exec_batch: row {} column {}: cannot bind {} to a {:?} column",
+                first_row + index,
+                column + 1,
+                value,
+                self.kind
+            ))
+        };
+        match slice {
+            AnySliceMut::NullableBit(mut slice) => {
+                for (i, value) in values.enumerate() {
+                    let cell = match value {
+                        Value::Null => None,
+                        Value::Bool(b) => Some(Bit::from_bool(*b)),
+                        other => return Err(mismatch(i, other)),
+                    };
+                    slice.set_cell(i, cell);
+                }
+            }
+            AnySliceMut::NullableI64(mut slice) => {
+                for (i, value) in values.enumerate() {
+                    let cell = bigint_cell(value).ok_or_else(|| mismatch(i, value))?;
+                    slice.set_cell(i, cell);
+                }
+            }
+            AnySliceMut::NullableF64(mut slice) => {
+                for (i, value) in values.enumerate() {
+                    let cell = double_cell(value).ok_or_else(|| mismatch(i, value))?;
+                    slice.set_cell(i, cell);
+                }
+            }
+            AnySliceMut::NullableDate(mut slice) => {
+                for (i, value) in values.enumerate() {
+                    let cell = date_cell(value).ok_or_else(|| mismatch(i, value))??;
+                    slice.set_cell(i, cell);
+                }
+            }
+            AnySliceMut::NullableTime(mut slice) => {
+                for (i, value) in values.enumerate() {
+                    let cell = time_cell(value).ok_or_else(|| mismatch(i, value))??;
+                    slice.set_cell(i, cell);
+                }
+            }
+            AnySliceMut::NullableTimestamp(mut slice) => {
+                for (i, value) in values.enumerate() {
+                    let cell = timestamp_cell(value).ok_or_else(|| mismatch(i, value))??;
+                    slice.set_cell(i, cell);
+                }
+            }
+            AnySliceMut::Text(mut slice) => {
+                let cells = values
+                    .enumerate()
+                    .map(|(i, value)| text_cell(value).ok_or_else(|| mismatch(i, value))?)
+                    .collect::<Result<Vec<_>, Error>>()?;
+                let max_len = cells.iter().flatten().map(|s| s.len()).max().unwrap_or(0);
+                slice.ensure_max_element_length(max_len, 0).map_err(odbc_error)?;
+                for (i, cell) in cells.iter().enumerate() {
+                    slice.set_cell(i, cell.as_deref().map(str::as_bytes));
+                }
+            }
+            AnySliceMut::Binary(mut slice) => {
+                let cells = values
+                    .enumerate()
+                    .map(|(i, value)| binary_cell(value).ok_or_else(|| mismatch(i, value)))
+                    .collect::<Result<Vec<_>, Error>>()?;
+                let max_len = cells.iter().flatten().map(|b| b.len()).max().unwrap_or(0);
+                slice.ensure_max_element_length(max_len, 0).map_err(odbc_error)?;
+                for (i, cell) in cells.into_iter().enumerate() {
+                    slice.set_cell(i, cell);
+                }
+            }
+            _ => return Err(Error::from(format!("exec_batch: unsupported column kind {:?}", self.kind))),
+        }
+        Ok(())
+    }
+}
+
+/// 整数列的值，不兼容时返回 `None`；超出 BIGINT 范围的 `U64` 同样不兼容
+pub fn bigint_cell(value: &Value) -> Option<Option<i64>> {
+    match value {
+        Value::Null => Some(None),
+        Value::I32(v) => Some(Some(*v as i64)),
+        Value::I64(v) => Some(Some(*v)),
+        Value::U32(v) => Some(Some(*v as i64)),
+        Value::U64(v) => i64::try_from(*v).ok().map(Some),
+        _ => None,
+    }
+}
+
+/// 浮点列的值，整数也可以写进浮点列
+pub fn double_cell(value: &Value) -> Option<Option<f64>> {
+    match value {
+        Value::Null => Some(None),
+        Value::F32(v) => Some(Some(*v as f64)),
+        Value::F64(v) => Some(Some(*v)),
+        Value::I32(v) => Some(Some(*v as f64)),
+        Value::I64(v) => Some(Some(*v as f64)),
+        Value::U32(v) => Some(Some(*v as f64)),
+        Value::U64(v) => Some(Some(*v as f64)),
+        _ => None,
+    }
+}
+
+fn date_cell(value: &Value) -> Option<Result<Option<Date>, Error>> {
+    match value {
+        Value::Null => Some(Ok(None)),
+        Value::Ext("Date", v) => Some(date_value(v.as_str().unwrap_or_default()).map(Some)),
+        _ => None,
+    }
+}
+
+fn time_cell(value: &Value) -> Option<Result<Option<Time>, Error>> {
+    match value {
+        Value::Null => Some(Ok(None)),
+        Value::Ext("Time", v) => Some(time_value(v.as_str().unwrap_or_default()).map(Some)),
+        _ => None,
+    }
+}
+
+fn timestamp_cell(value: &Value) -> Option<Result<Option<Timestamp>, Error>> {
+    match value {
+        Value::Null => Some(Ok(None)),
+        Value::Ext("DateTime", v) => Some(datetime_value(v.as_str().unwrap_or_default()).map(Some)),
+        Value::Ext("Timestamp", v) => {
+            let millis = v.as_i64().unwrap_or_default();
+            Some(Ok(Some(timestamp_value(fastdate::DateTime::from_timestamp_millis(millis)))))
+        }
+        _ => None,
+    }
+}
+
+/// 文本列的值，与单条执行时的文本编码相同；二进制值不兼容
+///
+/// ```
+/// use rbdc_dameng::batch::text_cell;
+/// use rbs::Value;
+///
+/// let decimal = Value::Ext("Decimal", Box::new(Value::String("1.50E+2".to_string())));
+/// assert_eq!(text_cell(&decimal).unwrap().unwrap().as_deref(), Some("150"));
+/// assert!(text_cell(&Value::Binary(vec![1])).is_none());
+/// ```
+pub fn text_cell(value: &Value) -> Option<Result<Option<Cow<'_, str>>, Error>> {
+    let text = match value {
+        Value::Null => return Some(Ok(None)),
+        Value::Binary(_) => return None,
+        Value::String(s) => Cow::Borrowed(s.as_str()),
+        Value::Ext("Decimal", v) => return Some(decimal_text(v.as_str().unwrap_or_default()).map(|s| Some(s.into()))),
+        Value::Ext(_, v) => match &**v {
+            Value::String(s) => Cow::Borrowed(s.as_str()),
+            Value::Binary(_) => return None,
+            other => Cow::Owned(other.to_string()),
+        },
+        Value::Array(_) | Value::Map(_) => match serde_json::to_string(value) {
+            Ok(s) => Cow::Owned(s),
+            Err(e) => return Some(Err(Error::from(e.to_string()))),
+        },
+        other => Cow::Owned(other.to_string()),
+    };
+    Some(Ok(Some(text)))
+}
+
+fn binary_cell(value: &Value) -> Option<Option<&[u8]>> {
+    match value {
+        Value::Null => Some(None),
+        Value::Binary(b) => Some(Some(b)),
+        Value::Ext(_, v) => match &**v {
+            Value::Binary(b) => Some(Some(b)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// 用参数数组执行预编译语句，每批 `batch_size` 行，每执行完一批调用一次 `on_batch`（参数为影响的行数）
+///
+/// 出错时错误信息前带有出错批次的行号范围，之前的批次已经执行。
+pub fn execute<S, F>(stmt: &mut Prepared<S>, rows: &[Vec<Value>], batch_size: usize, mut on_batch: F) -> Result<(), Error>
+where
+    S: AsStatementRef,
+    F: FnMut(u64),
+{
+    let Some(first) = rows.first() else {
+        return Ok(());
+    };
+    let num_columns = first.len();
+    if let Some(index) = rows.iter().position(|row| row.len() != num_columns) {
+        return Err(Error::from(format!(
+            "exec_batch: row {} has {} values, expected {}",
+            index,
+            rows[index].len(),
+            num_columns
+        )));
+    }
+    match stmt.num_params() {
+        Ok(n) if n as usize != num_columns => {
+            return Err(Error::from(format!(
+                "exec_batch: statement has {} parameters but rows have {} values",
+                n, num_columns
+            )));
+        }
+        _ => {}
+    }
+
+    let mut describe_supported = true;
+    let mut columns = Vec::with_capacity(num_columns);
+    for column in 0..num_columns {
+        let column = match ColumnKind::infer(rows, column) {
+            Some(kind) => BatchColumn { kind, described: None },
+            None => {
+                // 全部为 NULL，与单条执行相同，按参数的类型绑定
+                let described = if describe_supported {
+                    match stmt.describe_param(column as u16 + 1) {
+                        Ok(desc) => Some(desc.data_type),
+                        Err(e) => {
+                            log::debug!("SQLDescribeParam not supported, bind NULL as VARCHAR: {}", e);
+                            describe_supported = false;
+                            None
+                        }
+                    }
+                } else {
+                    None
+                };
+                BatchColumn {
+                    kind: ColumnKind::Text,
+                    described,
+                }
+            }
+        };
+        columns.push(column);
+    }
+
+    let capacity = batch_size.clamp(1, rows.len());
+    let buffers = columns
+        .iter()
+        .enumerate()
+        .map(|(index, column)| column.buffer(capacity, index))
+        .collect::<Result<Vec<_>, Error>>()?;
+    let stmt = stmt.as_stmt_ref();
+    let handle = stmt.as_sys();
+    // SAFETY: 语句已经准备好，缓冲区刚刚分配，没有超出元素长度的指示值
+    let mut inserter = unsafe { ColumnarBulkInserter::new(stmt, buffers, InOrder::new(num_columns)) }
+        .map_err(odbc_error)?;
+
+    for (index, batch) in rows.chunks(capacity).enumerate() {
+        let first_row = index * capacity;
+        inserter.set_num_rows(batch.len());
+        for (column, spec) in columns.iter().enumerate() {
+            spec.fill(inserter.column_mut(column), batch, column, first_row)?;
+        }
+        log::debug!("exec_batch: execute rows {}..{}", first_row, first_row + batch.len());
+        inserter.execute().map_err(|e| {
+            Error::from(format!(
+                "exec_batch: rows {}..{} failed: {}",
+                first_row,
+                first_row + batch.len(),
+                odbc_error(e)
+            ))
+        })?;
+        on_batch(row_count(handle));
+    }
+    Ok(())
+}
+
+/// 最近一次执行影响的行数，驱动不提供时为 0
+fn row_count(handle: odbc_api::sys::HStmt) -> u64 {
+    let mut count: isize = 0;
+    // SAFETY: 句柄属于仍在使用中的语句，SQLRowCount 只读取语句的状态
+    let ret = unsafe { SQLRowCount(handle, &mut count) };
+    if ret == SqlReturn::SUCCESS || ret == SqlReturn::SUCCESS_WITH_INFO {
+        count.max(0) as u64
+    } else {
+        0
+    }
+}
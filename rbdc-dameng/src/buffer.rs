@@ -0,0 +1,271 @@
+//! 列式取数缓冲区
+//!
+//! 整数、浮点、日期时间等定长类型直接绑定为原生的 C 类型，由驱动填充二进制值，
+//! 省去驱动格式化成文本再由 [`crate::decode`] 解析的开销；其余类型仍按文本 / 二进制取数。
+
+use std::mem::ManuallyDrop;
+use std::num::NonZeroUsize;
+
+use odbc_api::buffers::{AnySlice, BufferDesc, Indicator};
+use odbc_api::handles::{CDataMut, SqlResult, Statement, StatementImpl};
+use odbc_api::parameter::CElement;
+use odbc_api::sys::{HStmt, Lock, Operation, SQLSetPos, SetPosIRow, SqlDataType, SqlReturn};
+use odbc_api::Nullable;
+
+use crate::common::data_type::DmDataType;
+use crate::lob::{read_chunks, GetData, LOB_CHUNK_SIZE};
+use crate::options::TruncationPolicy;
+use crate::CellValue;
+
+/// 按列的 SQL 类型选择取数缓冲区
+///
+/// `max_str_len` 是文本 / 二进制列的长度上限，列声明的长度超过它时按上限截断。
+pub fn column_buffer_desc(data_type: DmDataType, nullable: bool, max_str_len: NonZeroUsize) -> BufferDesc {
+    match data_type {
+        DmDataType::SmallInt => BufferDesc::I16 { nullable },
+        DmDataType::Integer => BufferDesc::I32 { nullable },
+        DmDataType::BigInt => BufferDesc::I64 { nullable },
+        // NUMBER(*) 的精度为 0，只能按文本取
+        DmDataType::Numeric { precision, scale: 0 } | DmDataType::Decimal { precision, scale: 0 }
+            if (1..=9).contains(&precision) =>
+        {
+            BufferDesc::I32 { nullable }
+        }
+        DmDataType::Numeric { precision, scale: 0 } | DmDataType::Decimal { precision, scale: 0 }
+            if (10..=18).contains(&precision) =>
+        {
+            BufferDesc::I64 { nullable }
+        }
+        DmDataType::Real => BufferDesc::F32 { nullable },
+        DmDataType::Float { precision } if precision < 24 => BufferDesc::F32 { nullable },
+        DmDataType::Float { .. } | DmDataType::Double => BufferDesc::F64 { nullable },
+        DmDataType::Date => BufferDesc::Date { nullable },
+        // SQL_TIME_STRUCT 不含小数秒，带小数秒的 TIME 按文本取
+        DmDataType::Time { precision: 0 } => BufferDesc::Time { nullable },
+        DmDataType::Timestamp { .. } => BufferDesc::Timestamp { nullable },
+        _ if is_binary(data_type) => {
+            // 二进制列按字节数分配，不需要十六进制文本的两倍空间
+            BufferDesc::Binary {
+                length: data_type.column_size().unwrap_or(max_str_len).min(max_str_len).get(),
+            }
+        }
+        _ => BufferDesc::Text {
+            max_str_len: data_type.utf8_len().unwrap_or(max_str_len).min(max_str_len).get(),
+        },
+    }
+}
+
+/// 二进制类型，包括 BLOB / IMAGE
+pub fn is_binary(data_type: DmDataType) -> bool {
+    match data_type {
+        DmDataType::Binary { .. } | DmDataType::Varbinary { .. } | DmDataType::LongVarbinary { .. } => true,
+        DmDataType::Other { data_type, .. } => matches!(
+            data_type,
+            SqlDataType::EXT_BINARY | SqlDataType::EXT_VAR_BINARY | SqlDataType::EXT_LONG_VAR_BINARY
+        ),
+        _ => false,
+    }
+}
+
+/// CLOB / TEXT / BLOB / IMAGE 等大字段，总是通过 `SQLGetData` 分段读取完整的值
+pub fn is_lob(data_type: DmDataType) -> bool {
+    match data_type {
+        DmDataType::LongVarchar { .. } | DmDataType::WLongVarchar { .. } | DmDataType::LongVarbinary { .. } => true,
+        DmDataType::Other { data_type, .. } => matches!(
+            data_type,
+            SqlDataType::EXT_LONG_VARCHAR | SqlDataType::EXT_W_LONG_VARCHAR | SqlDataType::EXT_LONG_VAR_BINARY
+        ),
+        _ => false,
+    }
+}
+
+/// 文本 / 二进制列的声明长度超过上限或者未知（如 TEXT、CLOB），绑定缓冲区无法容纳完整的值
+pub fn exceeds_buffer(data_type: DmDataType, max_str_len: NonZeroUsize) -> bool {
+    let len = match column_buffer_desc(data_type, true, max_str_len) {
+        BufferDesc::Binary { .. } => data_type.column_size(),
+        BufferDesc::Text { .. } => data_type.utf8_len(),
+        _ => return false,
+    };
+    len.is_none_or(|len| len > max_str_len)
+}
+
+/// 值可能放不下绑定缓冲区、需要用 `SQLGetData` 读取完整值的列：大字段总是如此，
+/// `AutoGrow` 策略下还包括声明长度超过 `max_str_len` 或长度未知的列
+pub fn needs_get_data(data_type: DmDataType, truncation: TruncationPolicy, max_str_len: NonZeroUsize) -> bool {
+    is_lob(data_type) || (truncation == TruncationPolicy::AutoGrow && exceeds_buffer(data_type, max_str_len))
+}
+
+/// 结果集各列的取数方式
+///
+/// 普通列绑定到列式缓冲区按块取回；长列（见 [`needs_get_data`]）在每取回一块后，用 `SQLSetPos`
+/// 逐行定位再通过 `SQLGetData` 读取完整的值。`SQLGetData` 只能读取最后一个绑定列之后的列，
+/// 所以从第一个长列开始，后面的列都改用 `SQLGetData`。
+///
+/// 驱动不支持在块游标上调用 `SQLGetData`（`block_get_data` 为 `false`）时，有长列就整行逐行读取。
+///
+/// ```
+/// use std::num::NonZeroUsize;
+///
+/// use rbdc_dameng::buffer::FetchPlan;
+/// use rbdc_dameng::common::data_type::DmDataType;
+/// use rbdc_dameng::options::TruncationPolicy;
+///
+/// let types = [DmDataType::Integer, DmDataType::Varchar { length: NonZeroUsize::new(20) }, DmDataType::LongVarchar { length: None }];
+/// let plan = FetchPlan::new(&types, TruncationPolicy::AutoGrow, NonZeroUsize::new(100).unwrap(), true);
+/// assert_eq!(plan.bound, vec![0, 1]);
+/// assert_eq!(plan.get_data, vec![2]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FetchPlan {
+    /// 绑定到列式缓冲区、按块取回的列（从 0 开始）
+    pub bound: Vec<usize>,
+    /// 逐行用 `SQLGetData` 读取的列（从 0 开始）
+    pub get_data: Vec<usize>,
+}
+
+impl FetchPlan {
+    pub fn new(
+        types: &[DmDataType],
+        truncation: TruncationPolicy,
+        max_str_len: NonZeroUsize,
+        block_get_data: bool,
+    ) -> Self {
+        let first_long = types
+            .iter()
+            .position(|t| needs_get_data(*t, truncation, max_str_len))
+            .unwrap_or(types.len());
+        let first_long = if block_get_data || first_long == types.len() { first_long } else { 0 };
+        Self {
+            bound: (0..first_long).collect(),
+            get_data: (first_long..types.len()).collect(),
+        }
+    }
+
+    /// 没有可以绑定的列，不使用块游标
+    pub fn is_row_by_row(&self) -> bool {
+        self.bound.is_empty()
+    }
+}
+
+/// 块游标刚取回的一块中的某一行，用 `SQLSetPos` 定位后可以通过 `SQLGetData` 读取未绑定的列
+pub struct BlockRow {
+    /// 只借用句柄，不负责释放
+    stmt: ManuallyDrop<StatementImpl<'static>>,
+}
+
+impl BlockRow {
+    /// 定位到当前块的第 `row` 行（从 0 开始）
+    ///
+    /// # Safety
+    ///
+    /// `handle` 必须是刚取回一块数据、仍处于游标状态的语句，`row` 小于这一块的行数；
+    /// 返回值只能在取下一块之前使用。
+    pub unsafe fn position(handle: HStmt, row: usize) -> Result<Self, odbc_api::Error> {
+        let stmt = ManuallyDrop::new(unsafe { StatementImpl::new(handle) });
+        // SAFETY: 由调用方保证句柄有效且处于游标状态，SQL_POSITION 只移动游标，不修改数据
+        let ret = unsafe { SQLSetPos(handle, (row + 1) as SetPosIRow, Operation::POSITION, Lock::NO_CHANGE) };
+        let result = match ret {
+            SqlReturn::SUCCESS => SqlResult::Success(()),
+            SqlReturn::SUCCESS_WITH_INFO => SqlResult::SuccessWithInfo(()),
+            _ => SqlResult::Error { function: "SQLSetPos" },
+        };
+        result.into_result(&*stmt)?;
+        Ok(Self { stmt })
+    }
+}
+
+impl GetData for BlockRow {
+    fn get_data(&mut self, col: u16, target: &mut (impl CElement + CDataMut)) -> Result<(), odbc_api::Error> {
+        self.stmt.get_data(col, target).into_result(&*self.stmt)
+    }
+}
+
+/// 用 `SQLGetData` 分段读取完整的文本 / 二进制值，`None` 表示 SQL NULL
+pub fn get_long(row: &mut impl GetData, col: u16, binary: bool) -> Result<Option<CellValue>, odbc_api::Error> {
+    let mut value = vec![];
+    let not_null = read_chunks(row, col, binary, LOB_CHUNK_SIZE, |chunk| {
+        value.extend(chunk);
+        true
+    })?;
+    Ok(not_null.then_some(CellValue::Bytes(value)))
+}
+
+/// 一行在取数缓冲区中占用的字节数（含指示器），缓冲区总大小为 `行数 * 行宽`
+pub fn row_width(descs: &[BufferDesc]) -> usize {
+    descs.iter().map(|desc| desc.bytes_per_row()).sum()
+}
+
+/// 读取某一列第 `row` 行的值，`None` 表示 SQL NULL
+pub fn cell_at(column: AnySlice<'_>, row: usize) -> Option<CellValue> {
+    match column {
+        AnySlice::Text(view) => view.get(row).map(|v| CellValue::Bytes(v.to_vec())),
+        AnySlice::WText(view) => view
+            .get(row)
+            .map(|v| CellValue::Bytes(String::from_utf16_lossy(v).into_bytes())),
+        AnySlice::Binary(view) => view.get(row).map(|v| CellValue::Bytes(v.to_vec())),
+        AnySlice::Date(s) => Some(CellValue::Date(s[row])),
+        AnySlice::Time(s) => Some(CellValue::Time(s[row])),
+        AnySlice::Timestamp(s) => Some(CellValue::Timestamp(s[row])),
+        AnySlice::F64(s) => Some(CellValue::F64(s[row])),
+        AnySlice::F32(s) => Some(CellValue::F32(s[row])),
+        AnySlice::I8(s) => Some(CellValue::I16(s[row] as i16)),
+        AnySlice::I16(s) => Some(CellValue::I16(s[row])),
+        AnySlice::I32(s) => Some(CellValue::I32(s[row])),
+        AnySlice::I64(s) => Some(CellValue::I64(s[row])),
+        AnySlice::U8(s) => Some(CellValue::I16(s[row] as i16)),
+        AnySlice::Bit(s) => Some(CellValue::I16(s[row].as_bool() as i16)),
+        AnySlice::NullableDate(s) => s.get(row).map(|v| CellValue::Date(*v)),
+        AnySlice::NullableTime(s) => s.get(row).map(|v| CellValue::Time(*v)),
+        AnySlice::NullableTimestamp(s) => s.get(row).map(|v| CellValue::Timestamp(*v)),
+        AnySlice::NullableF64(s) => s.get(row).map(|v| CellValue::F64(*v)),
+        AnySlice::NullableF32(s) => s.get(row).map(|v| CellValue::F32(*v)),
+        AnySlice::NullableI8(s) => s.get(row).map(|v| CellValue::I16(*v as i16)),
+        AnySlice::NullableI16(s) => s.get(row).map(|v| CellValue::I16(*v)),
+        AnySlice::NullableI32(s) => s.get(row).map(|v| CellValue::I32(*v)),
+        AnySlice::NullableI64(s) => s.get(row).map(|v| CellValue::I64(*v)),
+        AnySlice::NullableU8(s) => s.get(row).map(|v| CellValue::I16(*v as i16)),
+        AnySlice::NullableBit(s) => s.get(row).map(|v| CellValue::I16(v.as_bool() as i16)),
+    }
+}
+
+/// 列中被截断的值的实际长度，没有截断时返回 `None`
+pub fn truncated_len(column: AnySlice<'_>) -> Option<Indicator> {
+    match column {
+        AnySlice::Text(view) => view.has_truncated_values(),
+        AnySlice::Binary(view) => view.has_truncated_values(),
+        _ => None,
+    }
+}
+
+/// 通过 `SQLGetData` 读取当前行某一列的值，文本 / 二进制分段读取直到取完，不受缓冲区长度限制
+///
+/// `desc` 是按 [`column_buffer_desc`] 选出的缓冲区，用来决定按哪种 C 类型取值。
+pub fn get_cell(row: &mut impl GetData, col: u16, desc: BufferDesc) -> Result<Option<CellValue>, odbc_api::Error> {
+    Ok(match desc {
+        BufferDesc::Text { .. } | BufferDesc::WText { .. } => get_long(row, col, false)?,
+        BufferDesc::Binary { .. } => get_long(row, col, true)?,
+        BufferDesc::Date { .. } => get_native(row, col)?.map(CellValue::Date),
+        BufferDesc::Time { .. } => get_native(row, col)?.map(CellValue::Time),
+        BufferDesc::Timestamp { .. } => get_native(row, col)?.map(CellValue::Timestamp),
+        BufferDesc::F64 { .. } => get_native(row, col)?.map(CellValue::F64),
+        BufferDesc::F32 { .. } => get_native(row, col)?.map(CellValue::F32),
+        BufferDesc::I8 { .. } => get_native::<i8>(row, col)?.map(|v| CellValue::I16(v as i16)),
+        BufferDesc::I16 { .. } => get_native(row, col)?.map(CellValue::I16),
+        BufferDesc::I32 { .. } => get_native(row, col)?.map(CellValue::I32),
+        BufferDesc::I64 { .. } => get_native(row, col)?.map(CellValue::I64),
+        BufferDesc::U8 { .. } => get_native::<u8>(row, col)?.map(|v| CellValue::I16(v as i16)),
+        BufferDesc::Bit { .. } => {
+            get_native::<odbc_api::Bit>(row, col)?.map(|v| CellValue::I16(v.as_bool() as i16))
+        }
+    })
+}
+
+fn get_native<T>(row: &mut impl GetData, col: u16) -> Result<Option<T>, odbc_api::Error>
+where
+    T: Default,
+    Nullable<T>: CElement + CDataMut,
+{
+    let mut value = Nullable::<T>::null();
+    row.get_data(col, &mut value)?;
+    Ok(value.into_opt())
+}
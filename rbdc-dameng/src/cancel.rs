@@ -0,0 +1,132 @@
+//! 取消正在执行的语句
+//!
+//! 语句在连接的工作线程上同步执行，丢弃返回的 future 并不会停止它。每次调用创建一个
+//! [`CancelToken`]：执行线程在语句准备好后登记语句句柄，future（或流、读取器）被丢弃时
+//! [`CancelOnDrop`] 对登记的句柄调用 `SQLCancel`，正在执行的语句随即以取消错误结束。
+//!
+//! 取消后返回的错误统一为 [`cancelled_error`]，可以用 `is_cancelled` 判断。
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use odbc_api::handles::{AsStatementRef, Statement};
+use odbc_api::sys::{HStmt, SQLCancel, SqlReturn};
+use rbdc::Error;
+
+use crate::common::error::{DamengError, DiagRecord};
+
+/// 语句句柄，只在登记期间（语句释放之前）使用
+struct StmtHandle(HStmt);
+
+// SAFETY: ODBC 允许在其它线程上对正在执行的语句调用 SQLCancel，句柄只在语句释放前使用，
+// 见 `Registration` 的 Drop
+unsafe impl Send for StmtHandle {}
+
+#[derive(Default)]
+struct Inner {
+    cancelled: AtomicBool,
+    stmt: Mutex<Option<StmtHandle>>,
+}
+
+/// 一次调用的取消标记，克隆共享同一个标记
+#[derive(Clone, Default)]
+pub struct CancelToken {
+    inner: Arc<Inner>,
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 标记为已取消，正在执行的语句通过 `SQLCancel` 中止，之后的语句不再执行
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::SeqCst);
+        let stmt = self.inner.stmt.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(StmtHandle(handle)) = &*stmt {
+            // SAFETY: 句柄在登记期间有效，注销需要先拿到同一把锁
+            let ret = unsafe { SQLCancel(*handle) };
+            if ret == SqlReturn::ERROR || ret == SqlReturn::INVALID_HANDLE {
+                log::warn!("SQLCancel failed: {:?}", ret);
+            } else {
+                log::debug!("statement cancelled");
+            }
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// 登记即将执行的语句，返回的守卫释放时注销，守卫必须在语句之前释放
+    ///
+    /// 已经取消时不再执行，直接返回取消错误。
+    pub(crate) fn register(&self, stmt: &mut impl AsStatementRef) -> Result<Registration<'_>, Error> {
+        let mut registered = self.inner.stmt.lock().unwrap_or_else(PoisonError::into_inner);
+        if self.is_cancelled() {
+            return Err(cancelled_error());
+        }
+        *registered = Some(StmtHandle(stmt.as_stmt_ref().as_sys()));
+        Ok(Registration { token: self })
+    }
+
+    /// 已取消时返回取消错误，用于不登记语句的操作（如事务控制语句）开始之前
+    ///
+    /// 工作线程上排队的操作在 future 被丢弃后仍会执行，必须先检查标记。
+    pub fn check_cancelled(&self) -> Result<(), Error> {
+        if self.is_cancelled() {
+            return Err(cancelled_error());
+        }
+        Ok(())
+    }
+
+    /// 已取消时把语句返回的错误替换为取消错误，驱动返回的错误信息各不相同
+    pub fn check(&self, e: Error) -> Error {
+        if self.is_cancelled() {
+            cancelled_error()
+        } else {
+            e
+        }
+    }
+}
+
+/// 语句句柄的登记，释放时注销
+pub(crate) struct Registration<'a> {
+    token: &'a CancelToken,
+}
+
+impl Drop for Registration<'_> {
+    fn drop(&mut self) {
+        *self.token.inner.stmt.lock().unwrap_or_else(PoisonError::into_inner) = None;
+    }
+}
+
+/// 释放时取消对应的调用，放在返回给调用方的 future / 流中
+///
+/// 调用已经结束时没有登记的语句，取消什么也不做。
+pub struct CancelOnDrop(pub CancelToken);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.cancel();
+    }
+}
+
+/// 语句被取消时返回的错误，SQLSTATE 为 ODBC 规定的 `HY008`
+///
+/// ```
+/// use rbdc_dameng::cancel::cancelled_error;
+/// use rbdc_dameng::common::error::DamengErrorExt;
+///
+/// let e = cancelled_error();
+/// assert!(e.is_cancelled());
+/// assert!(!e.is_retryable());
+/// ```
+pub fn cancelled_error() -> Error {
+    DamengError::from_records(vec![DiagRecord {
+        sqlstate: "HY008".to_string(),
+        native_code: 0,
+        message: "statement cancelled".to_string(),
+    }])
+    .into()
+}
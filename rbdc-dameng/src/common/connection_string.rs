@@ -0,0 +1,127 @@
+//! ODBC 连接串的解析与转义
+//!
+//! 按 `SQLDriverConnect` 的语法处理 `KEY=VALUE;KEY=VALUE`：
+//!
+//! - 关键字不区分大小写，首尾空白会被忽略；
+//! - 值可以用 `{}` 包裹，包裹的值里可以出现 `;`、`=`、`{`，`}` 需要写成 `}}`；
+//! - 未包裹的值从第一个 `=` 之后直到下一个 `;`，首尾空白会被忽略；
+//! - 同一个关键字出现多次时以第一次出现的为准（与 ODBC 驱动管理器一致）。
+
+use rbdc::Error;
+
+/// 解析连接串，按出现顺序返回去重后的键值对
+///
+/// ```
+/// use rbdc_dameng::common::connection_string;
+///
+/// let pairs = connection_string::parse("Driver={DM8 ODBC Driver};PWD={a;b}}c};uid=SYSDBA").unwrap();
+/// assert_eq!(connection_string::get(&pairs, "PWD"), Some("a;b}c"));
+/// assert_eq!(connection_string::get(&pairs, "UID"), Some("SYSDBA"));
+/// ```
+pub fn parse(s: &str) -> Result<Vec<(String, String)>, Error> {
+    let mut pairs: Vec<(String, String)> = vec![];
+    let mut rest = s;
+
+    loop {
+        rest = rest.trim_start();
+        if rest.is_empty() {
+            break;
+        }
+        // 空的片段，如 `;;`
+        if let Some(r) = rest.strip_prefix(';') {
+            rest = r;
+            continue;
+        }
+
+        let eq = match rest.find(['=', ';']) {
+            Some(i) if rest.as_bytes()[i] == b'=' => i,
+            _ => {
+                let segment = rest.split(';').next().unwrap_or_default();
+                return Err(Error::from(format!(
+                    "invalid connection string: missing '=' after '{}'",
+                    segment.trim()
+                )));
+            }
+        };
+        let key = rest[..eq].trim();
+        if key.is_empty() {
+            return Err(Error::from("invalid connection string: empty keyword"));
+        }
+        rest = rest[eq + 1..].trim_start();
+
+        let value;
+        if let Some(r) = rest.strip_prefix('{') {
+            let (v, r) = read_braced(r).ok_or_else(|| {
+                Error::from(format!(
+                    "invalid connection string: unterminated '{{' in value of '{}'",
+                    key
+                ))
+            })?;
+            value = v;
+            rest = r.trim_start();
+            match rest.strip_prefix(';') {
+                Some(r) => rest = r,
+                None if rest.is_empty() => {}
+                None => {
+                    return Err(Error::from(format!(
+                        "invalid connection string: unexpected characters after '}}' in value of '{}'",
+                        key
+                    )));
+                }
+            }
+        } else {
+            let end = rest.find(';').unwrap_or(rest.len());
+            value = rest[..end].trim_end().to_string();
+            rest = rest.get(end + 1..).unwrap_or_default();
+        }
+
+        if !pairs.iter().any(|(k, _)| k.eq_ignore_ascii_case(key)) {
+            pairs.push((key.to_string(), value));
+        }
+    }
+    Ok(pairs)
+}
+
+/// 读取 `{` 之后的内容直到未转义的 `}`，返回解码后的值和剩余部分
+fn read_braced(s: &str) -> Option<(String, &str)> {
+    let mut value = String::new();
+    let mut chars = s.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if c == '}' {
+            if let Some((_, '}')) = chars.peek() {
+                chars.next();
+                value.push('}');
+            } else {
+                return Some((value, &s[i + 1..]));
+            }
+        } else {
+            value.push(c);
+        }
+    }
+    None
+}
+
+/// 按关键字（不区分大小写）查找值
+pub fn get<'a>(pairs: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    pairs
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(key))
+        .map(|(_, v)| v.as_str())
+}
+
+/// 转义连接串中的值：包含 `;`、`{`、`}`、`=` 或首尾空白时用 `{}` 包裹，并把 `}` 写成 `}}`
+pub fn escape_value(value: &str) -> String {
+    let needs_quote = value.contains([';', '{', '}', '='])
+        || value.starts_with(char::is_whitespace)
+        || value.ends_with(char::is_whitespace);
+    if needs_quote {
+        quote_value(value)
+    } else {
+        value.to_owned()
+    }
+}
+
+/// 无条件用 `{}` 包裹，驱动名惯例总是这样写
+pub fn quote_value(value: &str) -> String {
+    format!("{{{}}}", value.replace('}', "}}"))
+}
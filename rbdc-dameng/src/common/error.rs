@@ -1,133 +1,395 @@
-// use std::{
-//     char::DecodeUtf16Error,
-//     num::{ParseFloatError, ParseIntError, TryFromIntError},
-//     str::ParseBoolError,
-// };
-//
-// use chrono::ParseError;
-// pub use odbc_error::OdbcWrapperError;
-//
-// use rbdc::{datetime::DateTime, Error};
-//
-// impl Default for Error {
-//     fn default() -> Self {
-//         Error::StringError(String::new())
-//     }
-// }
-//
-// impl From<&str> for Error {
-//     fn from(e: &str) -> Self {
-//         Error::StringError(e.into())
-//     }
-// }
-//
-// impl From<String> for Error {
-//     fn from(e: String) -> Self {
-//         Error::StringError(e)
-//     }
-// }
-//
-// impl From<odbc_api::Error> for Error {
-//     fn from(e: odbc_api::Error) -> Self {
-//         Error::OdbcError(e.into())
-//     }
-// }
-//
-// impl From<TryFromIntError> for Error {
-//     fn from(e: TryFromIntError) -> Self {
-//         Error::TypeConversionError(e.to_string())
-//     }
-// }
-//
-// impl From<ParseIntError> for Error {
-//     fn from(e: ParseIntError) -> Self {
-//         Error::TypeConversionError(e.to_string())
-//     }
-// }
-//
-// impl From<ParseBoolError> for Error {
-//     fn from(e: ParseBoolError) -> Self {
-//         Error::TypeConversionError(e.to_string())
-//     }
-// }
-//
-// impl From<ParseFloatError> for Error {
-//     fn from(e: ParseFloatError) -> Self {
-//         Error::TypeConversionError(e.to_string())
-//     }
-// }
-//
-// impl From<ParseError> for Error {
-//     fn from(e: ParseError) -> Self {
-//         Error::TypeConversionError(e.to_string())
-//     }
-// }
-//
-// impl From<DecodeUtf16Error> for Error {
-//     fn from(e: DecodeUtf16Error) -> Self {
-//         Error::OdbcError(OdbcWrapperError::DataHandlerError(e.to_string()))
-//     }
-// }
-//
-// pub mod odbc_error {
-//     use odbc_api::handles::slice_to_cow_utf8;
-//     use std::fmt::{self, Display, Formatter};
-//     use std::str::Utf8Error;
-//     use rbdc::Error;
-//
-//     #[derive(Debug, Error)]
-//     pub enum OdbcWrapperError {
-//         #[error("data handler error:`{0}`")]
-//         DataHandlerError(String),
-//         #[error("statement error:`{0}`")]
-//         StatementError(StatementError),
-//     }
-//
-//     #[derive(Debug, Error)]
-//     pub struct StatementError {
-//         pub state: String,
-//         pub error_msg: String,
-//     }
-//
-//     impl Display for StatementError {
-//         fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-//             write!(
-//                 f,
-//                 "state: {:?}, error_msg: {:?}",
-//                 self.state, self.error_msg
-//             )
-//         }
-//     }
-//
-//     impl From<Utf8Error> for OdbcWrapperError {
-//         fn from(error: Utf8Error) -> Self {
-//             OdbcWrapperError::DataHandlerError(error.to_string())
-//         }
-//     }
-//
-//     impl From<odbc_api::Error> for OdbcWrapperError {
-//         fn from(error: odbc_api::Error) -> Self {
-//             match &error {
-//                 odbc_api::Error::Diagnostics { record, .. }
-//                 | odbc_api::Error::UnsupportedOdbcApiVersion(record)
-//                 | odbc_api::Error::InvalidRowArraySize { record, .. }
-//                 | odbc_api::Error::UnableToRepresentNull(record)
-//                 | odbc_api::Error::OracleOdbcDriverDoesNotSupport64Bit(record) => {
-//                     let msg_info = slice_to_cow_utf8(&record.message);
-//                     let state = match std::str::from_utf8(&record.state.0) {
-//                         Ok(state_data) => state_data,
-//                         Err(e) => {
-//                             return OdbcWrapperError::DataHandlerError(e.to_string());
-//                         }
-//                     };
-//                     return OdbcWrapperError::StatementError(StatementError {
-//                         state: state.to_string(),
-//                         error_msg: msg_info.to_string(),
-//                     });
-//                 }
-//                 _ => {}
-//             }
-//             OdbcWrapperError::DataHandlerError(error.to_string())
-//         }
-//     }
-// }
+//! 结构化的数据库错误
+//!
+//! rbdc 的错误只有一个字符串（`Error::E(String)`），这里把 ODBC 诊断记录中的 SQLSTATE、
+//! 达梦错误码和错误信息按固定格式写进字符串：
+//!
+//! ```text
+//! [SQLSTATE 23000, DM -6602] 违反表[T]唯一性约束.
+//! ```
+//!
+//! 多条诊断记录按行分隔，前面可以带任意的上下文（如 `commit failed: `）。调用方拿到
+//! [`Error`] 后用 [`DamengError::from_error`] 或 [`DamengErrorExt::dameng`] 解析回来，
+//! 再用 [`DamengError::kind`] 或 `is_unique_violation` 等方法判断错误的类别。
+
+use std::fmt;
+
+use odbc_api::handles::{slice_to_cow_utf8, Record};
+
+pub use rbs::err_protocol;
+pub use rbs::Error;
+
+/// 一条 ODBC 诊断记录
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiagRecord {
+    /// 5 位 SQLSTATE，如 `23000`
+    pub sqlstate: String,
+    /// 达梦错误码，如 `-6602`
+    pub native_code: i32,
+    pub message: String,
+}
+
+impl From<&Record> for DiagRecord {
+    fn from(record: &Record) -> Self {
+        Self {
+            sqlstate: record.state.as_str().to_string(),
+            native_code: record.native_error,
+            message: slice_to_cow_utf8(&record.message).trim_end().to_string(),
+        }
+    }
+}
+
+impl fmt::Display for DiagRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[SQLSTATE {}, DM {}] {}", self.sqlstate, self.native_code, self.message)
+    }
+}
+
+const RECORD_MARKER: &str = "[SQLSTATE ";
+
+impl DiagRecord {
+    /// 解析 `Display` 输出的单条记录，`s` 必须以 `[SQLSTATE ` 开头
+    fn parse(s: &str) -> Option<Self> {
+        let rest = s.strip_prefix(RECORD_MARKER)?;
+        let (sqlstate, rest) = rest.split_once(", DM ")?;
+        let (native_code, message) = rest.split_once("] ")?;
+        Some(Self {
+            sqlstate: sqlstate.to_string(),
+            native_code: native_code.parse().ok()?,
+            message: message.to_string(),
+        })
+    }
+}
+
+/// 错误的类别，由 [`classify`] 根据达梦错误码和 SQLSTATE 判断
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// 违反唯一性约束（含主键）
+    UniqueViolation,
+    /// 违反引用约束
+    ForeignKeyViolation,
+    /// 死锁或可串行化事务冲突，整个事务需要重做
+    Deadlock,
+    /// 等待锁超时
+    LockTimeout,
+    /// 连接已断开或无法建立
+    ConnectionBroken,
+    /// 语句执行超过了 `query_timeout`
+    Timeout,
+    /// 语句被取消，如调用方丢弃了查询的 future
+    Cancelled,
+    /// 表、视图或列不存在，通常是表结构在语句准备之后被修改过
+    SchemaChanged,
+    Other,
+}
+
+/// 按达梦错误码分类，优先于 SQLSTATE
+const NATIVE_CODE_KINDS: &[(i32, ErrorKind)] = &[
+    (-6602, ErrorKind::UniqueViolation),
+    // 插入 / 更新子表时父表中没有对应的记录
+    (-6625, ErrorKind::ForeignKeyViolation),
+    // 删除 / 更新父表时子表中仍有引用的记录
+    (-6626, ErrorKind::ForeignKeyViolation),
+    (-6403, ErrorKind::Deadlock),
+    (-6407, ErrorKind::LockTimeout),
+    // 网络通信异常、连接已断开
+    (-70019, ErrorKind::ConnectionBroken),
+    (-70028, ErrorKind::ConnectionBroken),
+    (-6001, ErrorKind::ConnectionBroken),
+    // 无效的表或视图名、无效的列名
+    (-2106, ErrorKind::SchemaChanged),
+    (-2111, ErrorKind::SchemaChanged),
+];
+
+/// 按 SQLSTATE 前缀分类，较长的前缀写在前面
+const SQLSTATE_KINDS: &[(&str, ErrorKind)] = &[
+    ("23505", ErrorKind::UniqueViolation),
+    ("23503", ErrorKind::ForeignKeyViolation),
+    ("40001", ErrorKind::Deadlock),
+    ("HYT00", ErrorKind::Timeout),
+    ("HYT01", ErrorKind::ConnectionBroken),
+    ("HY008", ErrorKind::Cancelled),
+    // 表或视图不存在、列不存在
+    ("42S02", ErrorKind::SchemaChanged),
+    ("42S22", ErrorKind::SchemaChanged),
+    // 08xxx：连接异常
+    ("08", ErrorKind::ConnectionBroken),
+];
+
+/// 按达梦错误码和 SQLSTATE 判断错误的类别，都不认识时返回 [`ErrorKind::Other`]
+pub fn classify(sqlstate: &str, native_code: i32) -> ErrorKind {
+    let by_code = NATIVE_CODE_KINDS.iter().find(|(code, _)| *code == native_code);
+    let by_state = || SQLSTATE_KINDS.iter().find(|(prefix, _)| sqlstate.starts_with(prefix));
+    by_code
+        .map(|(_, kind)| *kind)
+        .or_else(|| by_state().map(|(_, kind)| *kind))
+        .unwrap_or(ErrorKind::Other)
+}
+
+impl DiagRecord {
+    pub fn kind(&self) -> ErrorKind {
+        classify(&self.sqlstate, self.native_code)
+    }
+}
+
+/// 达梦驱动的错误
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DamengError {
+    /// 驱动或服务端返回的错误，`records` 是全部诊断记录，第一条与外层字段相同
+    Database {
+        sqlstate: String,
+        native_code: i32,
+        message: String,
+        records: Vec<DiagRecord>,
+    },
+    /// 没有诊断记录的错误，如参数编码失败、缓冲区分配失败等
+    Other(String),
+}
+
+impl DamengError {
+    /// 由诊断记录构造，`records` 不能为空
+    pub fn from_records(records: Vec<DiagRecord>) -> Self {
+        match records.first() {
+            Some(first) => DamengError::Database {
+                sqlstate: first.sqlstate.clone(),
+                native_code: first.native_code,
+                message: first.message.clone(),
+                records,
+            },
+            None => DamengError::Other("unknown database error".to_string()),
+        }
+    }
+
+    pub fn sqlstate(&self) -> Option<&str> {
+        match self {
+            DamengError::Database { sqlstate, .. } => Some(sqlstate),
+            DamengError::Other(_) => None,
+        }
+    }
+
+    pub fn native_code(&self) -> Option<i32> {
+        match self {
+            DamengError::Database { native_code, .. } => Some(*native_code),
+            DamengError::Other(_) => None,
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            DamengError::Database { message, .. } => message,
+            DamengError::Other(message) => message,
+        }
+    }
+
+    pub fn records(&self) -> &[DiagRecord] {
+        match self {
+            DamengError::Database { records, .. } => records,
+            DamengError::Other(_) => &[],
+        }
+    }
+
+    /// 错误的类别，取第一条能识别的诊断记录
+    pub fn kind(&self) -> ErrorKind {
+        self.records()
+            .iter()
+            .map(DiagRecord::kind)
+            .find(|kind| *kind != ErrorKind::Other)
+            .unwrap_or(ErrorKind::Other)
+    }
+
+    pub fn is_unique_violation(&self) -> bool {
+        self.kind() == ErrorKind::UniqueViolation
+    }
+
+    pub fn is_foreign_key_violation(&self) -> bool {
+        self.kind() == ErrorKind::ForeignKeyViolation
+    }
+
+    pub fn is_deadlock(&self) -> bool {
+        self.kind() == ErrorKind::Deadlock
+    }
+
+    pub fn is_lock_timeout(&self) -> bool {
+        self.kind() == ErrorKind::LockTimeout
+    }
+
+    pub fn is_connection_broken(&self) -> bool {
+        self.kind() == ErrorKind::ConnectionBroken
+    }
+
+    pub fn is_timeout(&self) -> bool {
+        self.kind() == ErrorKind::Timeout
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.kind() == ErrorKind::Cancelled
+    }
+
+    pub fn is_schema_changed(&self) -> bool {
+        self.kind() == ErrorKind::SchemaChanged
+    }
+
+    /// 重试可能成功的错误：死锁、锁超时、连接断开
+    ///
+    /// 事务中发生的错误需要从 `begin` 开始重做整个事务，而不只是重试出错的语句。
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self.kind(),
+            ErrorKind::Deadlock | ErrorKind::LockTimeout | ErrorKind::ConnectionBroken
+        )
+    }
+
+    /// 从 rbdc 的错误中解析出结构化的错误
+    ///
+    /// 字符串中带有诊断记录时返回 [`DamengError::Database`]，否则返回 [`DamengError::Other`]。
+    ///
+    /// ```
+    /// use rbdc_dameng::common::error::{DamengError, DiagRecord, Error};
+    ///
+    /// let e = DamengError::from_records(vec![DiagRecord {
+    ///     sqlstate: "23000".to_string(),
+    ///     native_code: -6602,
+    ///     message: "违反唯一性约束".to_string(),
+    /// }]);
+    /// let wrapped = Error::from(format!("exec failed: {}", e));
+    /// assert_eq!(DamengError::from_error(&wrapped), e);
+    /// ```
+    pub fn from_error(e: &Error) -> Self {
+        let Error::E(s) = e;
+        let Some(start) = s.find(RECORD_MARKER) else {
+            return DamengError::Other(s.clone());
+        };
+        let mut records: Vec<DiagRecord> = vec![];
+        for line in s[start..].split('\n') {
+            match DiagRecord::parse(line) {
+                Some(record) => records.push(record),
+                // 不是记录开头的行属于上一条记录的多行信息
+                None => match records.last_mut() {
+                    Some(last) => {
+                        last.message.push('\n');
+                        last.message.push_str(line);
+                    }
+                    None => return DamengError::Other(s.clone()),
+                },
+            }
+        }
+        Self::from_records(records)
+    }
+}
+
+impl fmt::Display for DamengError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DamengError::Database { records, .. } => {
+                for (i, record) in records.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str("\n")?;
+                    }
+                    write!(f, "{}", record)?;
+                }
+                Ok(())
+            }
+            DamengError::Other(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for DamengError {}
+
+impl From<odbc_api::Error> for DamengError {
+    fn from(e: odbc_api::Error) -> Self {
+        match &e {
+            odbc_api::Error::Diagnostics { record, .. }
+            | odbc_api::Error::UnsupportedOdbcApiVersion(record)
+            | odbc_api::Error::InvalidRowArraySize { record, .. }
+            | odbc_api::Error::UnableToRepresentNull(record)
+            | odbc_api::Error::OracleOdbcDriverDoesNotSupport64Bit(record) => {
+                Self::from_records(vec![DiagRecord::from(record)])
+            }
+            _ => DamengError::Other(e.to_string()),
+        }
+    }
+}
+
+impl From<DamengError> for Error {
+    fn from(e: DamengError) -> Self {
+        Error::E(e.to_string())
+    }
+}
+
+/// 把 ODBC 错误转换成保留了诊断信息的 rbdc 错误，用于 `map_err`
+pub fn odbc_error(e: odbc_api::Error) -> Error {
+    DamengError::from(e).into()
+}
+
+/// 在 rbdc 的 [`Error`] 上直接读取达梦错误信息
+///
+/// ```
+/// use rbdc_dameng::common::error::{DamengErrorExt, Error};
+///
+/// let e = Error::from("[SQLSTATE 08S01, DM -70028] 连接已断开");
+/// assert_eq!(e.sqlstate().as_deref(), Some("08S01"));
+/// assert_eq!(e.native_code(), Some(-70028));
+/// ```
+///
+/// 分类方法与 [`DamengError`] 上的同名方法相同：
+///
+/// ```
+/// use rbdc_dameng::common::error::{DamengErrorExt, Error};
+///
+/// let e = Error::from("[SQLSTATE 23000, DM -6602] 违反表[T]唯一性约束.");
+/// assert!(e.is_unique_violation());
+/// assert!(!e.is_retryable());
+/// ```
+pub trait DamengErrorExt {
+    fn dameng(&self) -> DamengError;
+
+    fn is_unique_violation(&self) -> bool {
+        self.dameng().is_unique_violation()
+    }
+
+    fn is_foreign_key_violation(&self) -> bool {
+        self.dameng().is_foreign_key_violation()
+    }
+
+    fn is_deadlock(&self) -> bool {
+        self.dameng().is_deadlock()
+    }
+
+    fn is_lock_timeout(&self) -> bool {
+        self.dameng().is_lock_timeout()
+    }
+
+    fn is_connection_broken(&self) -> bool {
+        self.dameng().is_connection_broken()
+    }
+
+    fn is_timeout(&self) -> bool {
+        self.dameng().is_timeout()
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.dameng().is_cancelled()
+    }
+
+    fn is_schema_changed(&self) -> bool {
+        self.dameng().is_schema_changed()
+    }
+
+    fn is_retryable(&self) -> bool {
+        self.dameng().is_retryable()
+    }
+
+    fn sqlstate(&self) -> Option<String> {
+        self.dameng().sqlstate().map(str::to_string)
+    }
+
+    fn native_code(&self) -> Option<i32> {
+        self.dameng().native_code()
+    }
+}
+
+impl DamengErrorExt for Error {
+    fn dameng(&self) -> DamengError {
+        DamengError::from_error(self)
+    }
+}
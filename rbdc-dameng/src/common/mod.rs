@@ -1,2 +1,4 @@
-pub mod data_type;
-pub mod error;
\ No newline at end of file
+pub mod connection_string;
+pub mod data_type;
+pub mod error;
+pub mod sql;
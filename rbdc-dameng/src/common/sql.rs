@@ -0,0 +1,206 @@
+//! 轻量的 SQL 识别，只用来判断语句的种类和目标表，不做完整的语法分析
+//!
+//! 扫描时跳过注释和字符串常量；未加引号的标识符按达梦的规则转成大写，
+//! 双引号包裹的标识符保持原样（`""` 表示一个 `"`）。
+
+/// INSERT 语句的目标表
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InsertTarget {
+    pub schema: Option<String>,
+    pub table: String,
+    /// 语句带有 `RETURNING` 子句，执行后会返回结果集
+    pub returning: bool,
+}
+
+impl InsertTarget {
+    /// `"模式名"."表名"` 形式的表名，可以直接放进 `IDENT_SEED('...')` 等函数的字符串参数
+    ///
+    /// 每一部分都加上双引号：解析时未加引号的名字已经转成大写，加引号的名字要保持原来的大小写。
+    pub fn qualified_name(&self) -> String {
+        match &self.schema {
+            Some(schema) => format!("{}.{}", quote_identifier(schema), quote_identifier(&self.table)),
+            None => quote_identifier(&self.table),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    /// 未加引号的关键字或标识符，已转成大写
+    Word(String),
+    /// 双引号包裹的标识符
+    Quoted(String),
+    Dot,
+    Other,
+}
+
+/// 识别 INSERT 语句的目标表，不是 INSERT 语句时返回 `None`
+///
+/// ```
+/// use rbdc_dameng::common::sql::parse_insert;
+///
+/// let target = parse_insert(r#"insert  into "sales"."Order"(id) values (?)"#).unwrap();
+/// assert_eq!(target.schema.as_deref(), Some("sales"));
+/// assert_eq!(target.table, "Order");
+///
+/// let target = parse_insert("INSERT INTO biz_activity VALUES (?)").unwrap();
+/// assert_eq!(target.qualified_name(), r#""BIZ_ACTIVITY""#);
+/// ```
+pub fn parse_insert(sql: &str) -> Option<InsertTarget> {
+    let mut tokens = Tokens::new(sql);
+    if tokens.next()? != Token::Word("INSERT".to_string()) || tokens.next()? != Token::Word("INTO".to_string()) {
+        return None;
+    }
+    let mut table = identifier(tokens.next()?)?;
+    let mut schema = None;
+    let mut next = tokens.next();
+    if next == Some(Token::Dot) {
+        schema = Some(table);
+        table = identifier(tokens.next()?)?;
+        next = tokens.next();
+    }
+    let returning = next
+        .into_iter()
+        .chain(tokens)
+        .any(|t| t == Token::Word("RETURNING".to_string()));
+    Some(InsertTarget {
+        schema,
+        table,
+        returning,
+    })
+}
+
+/// 会修改表结构或名字解析方式的语句：DDL 以及 `SET SCHEMA`，执行后缓存的预编译语句可能失效
+///
+/// ```
+/// use rbdc_dameng::common::sql::changes_schema;
+///
+/// assert!(changes_schema("/* migrate */ alter table t add c int"));
+/// assert!(changes_schema("SET SCHEMA sales"));
+/// assert!(!changes_schema("set transaction isolation level read committed"));
+/// assert!(!changes_schema("select * from t"));
+/// ```
+pub fn changes_schema(sql: &str) -> bool {
+    let mut tokens = Tokens::new(sql);
+    match tokens.next() {
+        Some(Token::Word(word)) => match word.as_str() {
+            "CREATE" | "ALTER" | "DROP" | "TRUNCATE" | "RENAME" | "COMMENT" => true,
+            "SET" => tokens.next() == Some(Token::Word("SCHEMA".to_string())),
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// 只读取数据的查询（`SELECT` / `WITH`），执行中连接断开时可以在新连接上重新执行
+///
+/// ```
+/// use rbdc_dameng::common::sql::is_query;
+///
+/// assert!(is_query("/* list */ select * from t"));
+/// assert!(is_query("WITH t AS (SELECT 1 FROM DUAL) SELECT * FROM t"));
+/// assert!(!is_query("update t set c = 1"));
+/// assert!(!is_query("begin select 1 into x from dual; end;"));
+/// ```
+pub fn is_query(sql: &str) -> bool {
+    matches!(Tokens::new(sql).next(), Some(Token::Word(word)) if word == "SELECT" || word == "WITH")
+}
+
+/// 在插入语句后追加 `SELECT SCOPE_IDENTITY()`，一起执行
+///
+/// `SCOPE_IDENTITY()` 只对同一个作用域（批处理）有效，单独执行时已经取不到前一条插入语句的值；
+/// 结果中第一个是插入影响的行数，第二个是只有一行一列的自增值。
+///
+/// ```
+/// use rbdc_dameng::common::sql::with_scope_identity;
+///
+/// assert_eq!(
+///     with_scope_identity("insert into t(name) values (?);\n"),
+///     "insert into t(name) values (?); SELECT SCOPE_IDENTITY()"
+/// );
+/// ```
+pub fn with_scope_identity(insert: &str) -> String {
+    let insert = insert.trim_end_matches(|c: char| c == ';' || c.is_whitespace());
+    format!("{}; SELECT SCOPE_IDENTITY()", insert)
+}
+
+/// 把标识符放进双引号中，`"` 写成 `""`
+pub fn quote_identifier(s: &str) -> String {
+    format!("\"{}\"", s.replace('"', "\"\""))
+}
+
+/// 把字符串放进 SQL 的单引号常量中，`'` 写成 `''`
+pub fn quote_literal(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+fn identifier(token: Token) -> Option<String> {
+    match token {
+        Token::Word(s) | Token::Quoted(s) => Some(s),
+        _ => None,
+    }
+}
+
+struct Tokens<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Tokens<'a> {
+    fn new(sql: &'a str) -> Self {
+        Self { rest: sql }
+    }
+
+    /// 跳过空白和注释
+    fn skip_trivia(&mut self) {
+        loop {
+            self.rest = self.rest.trim_start();
+            if let Some(r) = self.rest.strip_prefix("--") {
+                self.rest = r.find('\n').map_or("", |i| &r[i + 1..]);
+            } else if let Some(r) = self.rest.strip_prefix("/*") {
+                self.rest = r.find("*/").map_or("", |i| &r[i + 2..]);
+            } else {
+                return;
+            }
+        }
+    }
+}
+
+impl Iterator for Tokens<'_> {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Token> {
+        self.skip_trivia();
+        let c = self.rest.chars().next()?;
+        if c == '"' || c == '\'' {
+            // 引号内 连续两个引号表示引号本身
+            let mut value = String::new();
+            let mut chars = self.rest[1..].char_indices();
+            let mut end = self.rest.len();
+            while let Some((i, ch)) = chars.next() {
+                if ch == c {
+                    if self.rest[1 + i + 1..].starts_with(c) {
+                        value.push(c);
+                        chars.next();
+                        continue;
+                    }
+                    end = 1 + i + 1;
+                    break;
+                }
+                value.push(ch);
+            }
+            self.rest = &self.rest[end..];
+            return Some(if c == '"' { Token::Quoted(value) } else { Token::Other });
+        }
+        if c.is_alphanumeric() || c == '_' || c == '$' || c == '#' {
+            let end = self
+                .rest
+                .find(|ch: char| !(ch.is_alphanumeric() || ch == '_' || ch == '$' || ch == '#'))
+                .unwrap_or(self.rest.len());
+            let word = self.rest[..end].to_uppercase();
+            self.rest = &self.rest[end..];
+            return Some(Token::Word(word));
+        }
+        self.rest = &self.rest[c.len_utf8()..];
+        Some(if c == '.' { Token::Dot } else { Token::Other })
+    }
+}
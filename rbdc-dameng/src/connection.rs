@@ -1,446 +1,1394 @@
+use std::cell::Cell;
 use std::collections::HashMap;
+use std::future::Future;
 use std::num::NonZeroUsize;
-use std::sync::{Arc, Mutex, MutexGuard};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::task::Poll;
 
-use anyhow::anyhow;
 use futures_core::future::BoxFuture;
-use odbc_api::buffers::{BufferDesc, TextRowSet};
-use odbc_api::parameter::InputParameter;
-use odbc_api::{Connection as OdbcApiConnection, IntoParameter};
+use futures_core::Stream;
+use futures_util::{stream, StreamExt};
+use odbc_api::buffers::{AnyBuffer, BufferDesc, ColumnarAnyBuffer};
+use odbc_api::handles::{AsStatementRef, SqlResult, Statement, StatementImpl};
+use odbc_api::parameter::{BlobParam, InputParameter};
+use odbc_api::{Connection as OdbcApiConnection, Prepared};
 use odbc_api::{ConnectionOptions, Nullable};
-use odbc_api::{Cursor, Environment, Nullability, ResultSetMetadata};
+use odbc_api::{Cursor, CursorImpl, Environment, Nullability, ResultSetMetadata};
 use once_cell::sync::Lazy;
 use rbdc::db::{Connection, ExecResult, Row};
 use rbdc::Error;
 use rbs::Value;
-
-use crate::encode::Encode;
-use crate::options::DamengConnectOptions;
-use crate::{DamengColumn, DamengData, DamengRow};
+use tokio::sync::mpsc;
+
+use crate::batch::{self, BatchResult};
+use crate::buffer::{
+    cell_at, column_buffer_desc, get_cell, get_long, is_binary, row_width, truncated_len, BlockRow, FetchPlan,
+};
+use crate::cancel::{CancelOnDrop, CancelToken};
+use crate::common::error::{odbc_error, DamengError, DamengErrorExt};
+use crate::common::sql::{changes_schema, is_query, parse_insert, quote_literal, with_scope_identity, InsertTarget};
+use crate::decode::Decode;
+use crate::encode::encode_params_with;
+use crate::lob::{read_chunks, LobReader, MixedParams, Param, LOB_CHUNK_SIZE};
+use crate::options::{DamengConnectOptions, QueryOptions, TruncationPolicy, TxOptions, DEFAULT_MAX_STR_LEN};
+use crate::reconnect::with_reconnect;
+use crate::results::{collect_results, ResultSource};
+use crate::statement_cache::{CacheStats, StatementCache, WithOwner};
+use crate::transaction::{Transaction, TxCommand};
+use crate::worker::Worker;
+use crate::{DamengColumn, DamengData, DamengRow, ResultSet};
 
 static ENV: Lazy<Environment> = Lazy::new(|| Environment::new().unwrap());
 
+/// 按驱动缓存的 [`Session::probe_block_get_data`] 结果，见 [`Session::block_get_data`]
+static BLOCK_GET_DATA: Lazy<Mutex<HashMap<String, bool>>> = Lazy::new(Default::default);
+
+/// 工作线程编号，用于线程名
+static WORKER_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// 达梦连接
+///
+/// ODBC 连接由连接独占的工作线程持有（见 [`crate::worker`]），所有操作都发给该线程按顺序执行。
+/// 克隆共享同一个工作线程，最后一个克隆释放时线程退出，并回滚未结束的事务。
 #[derive(Clone)]
 pub struct DamengConnection {
-    pub conn: Arc<Mutex<OdbcApiConnection<'static>>>,
-    pub batch_size: usize, // 批量获取数据条数
-    pub max_str_len: Option<usize>, // 最大字符串长度
-    pub is_trans: Arc<Mutex<bool>>,
+    worker: Arc<Worker<Session>>,
+    /// 是否有未读完的流占着工作线程，克隆之间共享
+    streaming: Arc<AtomicBool>,
+    pub batch_size: usize,            // 批量获取数据条数
+    pub max_str_len: Option<usize>,   // 最大字符串长度
+    pub truncation: TruncationPolicy, // 值超过 max_str_len 时的处理方式
     pub sys_info: Option<String>,
+    /// 建立连接时的参数
+    pub options: Arc<DamengConnectOptions>,
 }
 
-unsafe impl Send for DamengConnection {}
-
-unsafe impl Sync for DamengConnection {}
-
 impl Connection for DamengConnection {
     fn get_rows(&mut self, sql: &str, params: Vec<Value>) -> BoxFuture<Result<Vec<Box<dyn Row>>, Error>> {
-        let oc = self.clone();
-        let sql = sql.to_string();
+        self.get_rows_with(sql, params, QueryOptions::default())
+    }
 
-        let nz_max_str_len = NonZeroUsize::new(self.max_str_len.unwrap_or(0)).unwrap();
+    fn exec(&mut self, sql: &str, params: Vec<Value>) -> BoxFuture<Result<ExecResult, Error>> {
+        self.exec_with(sql, params, QueryOptions::default())
+    }
 
-        let task = tokio::task::spawn_blocking(move || {
-            if sql.eq("begin") || sql.eq("commit") || sql.eq("rollback") {
-                log::warn!("不支持事务相关操作,直接返回");
-                return Err(rbdc::Error::from("不支持事务相关操作"));
-            }
-            // Execute the query as a one off, and pass the parameters.
-            let binding = oc.conn.clone();
-            let conn = binding.lock().map_err(|_err| Error::from(_err.to_string()))?;
+    fn ping(&mut self) -> BoxFuture<Result<(), rbdc::Error>> {
+        Box::pin(self.run(|session| {
+            // 连接断开时 run 会先重连，重连成功即视为连接可用
+            session.run(
+                &CancelToken::new(),
+                || true,
+                |session| {
+                    session
+                        .conn
+                        .execute("SELECT 1", (), None)
+                        .map(|_| ())
+                        .map_err(odbc_error)
+                },
+            )
+        }))
+    }
 
-            let mut results = Vec::new();
+    fn close(&mut self) -> BoxFuture<Result<(), rbdc::Error>> {
+        // 关闭前回滚未结束的事务，避免连接归还连接池后仍处于事务中
+        Box::pin(self.run(|session| session.tx.rollback_all(&*session.conn)))
+    }
+}
 
-            let mut encoded_params: Vec<String> = vec![];
-            for x in &params {
-                // encoded_params.push(x.encode(0)?) ;
-                encoded_params.push(x.clone().encode(0)?);
-                // encoded_params.push(value_to_json_string(x));
-            }
-            // log::debug!("encoded_params: {:?}",encoded_params);
-
-            // 执行查询
-            log::debug!("get_rows执行的sql:{}",sql);
-
-            // // Convert the input strings into parameters suitable to for use with ODBC.
-            // let params: Vec<_> = encoded_params
-            //     .iter()
-            //     .map(|param| param.as_str().into_parameter())
-            //     .collect();
-
-            // 创建拥有所有权的参数，而不是借用
-            let odbc_params: Vec<Box<dyn InputParameter>> = encoded_params
-                .into_iter()  // 使用 into_iter() 而不是 iter()
-                .map(|s| s.into_parameter())  // 直接使用 String 而不是 &str
-                .map(|p| Box::new(p) as Box<dyn InputParameter>)
-                .collect();
-
-            let mut stmt = conn.prepare(&sql)
-                .map_err(|_err| Error::from(_err.to_string()))?;
-
-            // if let Ok(Some(mut cursor)) = conn.execute(&sql, odbc_params.as_slice(), None) {
-            if let Ok(Some(mut cursor)) = stmt.execute(odbc_params.as_slice()) {
-                let mut columns: Vec<DamengColumn> = vec![];
-
-                let mut max_str_lens: Vec<usize> = vec![];
-
-                let mut column_description = Default::default();
-
-                for index in 1..=cursor.num_result_cols().unwrap_or(0) {
-                    cursor.describe_col(index as u16, &mut column_description)
-                        .map_err(|_err| Error::from(_err.to_string()))?;
-
-                    let nullable = matches!(
-                        column_description.nullability,
-                        Nullability::Unknown | Nullability::Nullable
-                    );
-                    let _desc = BufferDesc::from_data_type(
-                        column_description.data_type,
-                        nullable,
-                    ).unwrap_or(BufferDesc::Text { max_str_len: 255 });
-
-                    let mut max_str_len_for_column = column_description.data_type.utf8_len().unwrap_or(nz_max_str_len);
-                    if max_str_len_for_column > nz_max_str_len {
-                        max_str_len_for_column = nz_max_str_len
-                    }
-                    max_str_lens.push(max_str_len_for_column.get());
+/// `SCOPE_IDENTITY()` 返回的自增值，按驱动报告的类型可能是整数或数字文本
+fn identity_value(value: Value) -> Value {
+    let id = value.as_i64().or_else(|| value.as_str().and_then(|s| s.parse().ok()));
+    id.map_or(Value::Null, Value::I64)
+}
 
-                    columns.push(DamengColumn {
-                        name: column_description.name_to_string().unwrap_or("".to_string()).to_lowercase(),
-                        column_type: column_description.data_type,
-                        nullability: nullable,
-                    });
-                }
+/// 取数缓冲区放不下某个值时的错误信息
+fn truncation_error(error: odbc_api::Error, columns: &[DamengColumn], max_str_len: NonZeroUsize) -> Error {
+    match error {
+        odbc_api::Error::TooLargeValueForBuffer {
+            indicator,
+            buffer_index,
+        } => {
+            let col_name = &columns[buffer_index].name;
+            let required = match indicator {
+                Some(required) => format!("{} bytes", required),
+                None => "an unknown length".to_string(),
+            };
+            Error::from(format!(
+                "value of column '{}' is truncated: it needs {} but max_str_len is {}. \
+                 Increase max_str_len or use TruncationPolicy::AutoGrow to fetch the complete value",
+                col_name, required, max_str_len
+            ))
+        }
+        other => odbc_error(other),
+    }
+}
 
-                // println!("columns: {:?}", columns);
-
-                let mut buffer = match TextRowSet::from_max_str_lens(columns.len(), max_str_lens) {
-                    Ok(buffers) => buffers,
-                    Err(_err) => { return Err(rbdc::Error::from("TextRowSet::for_cursor() err")); }
-                };
-
-                let mut row_set_cursor = match cursor.bind_buffer(&mut buffer) {
-                    Ok(block_cursor) => block_cursor,
-                    Err(_err) => { return Err(rbdc::Error::from("cursor.bind_buffer() err")); }
-                };
-
-                while let Some(buffer) = row_set_cursor
-                    .fetch_with_truncation_check(false)
-                    .map_err(|error| provide_context_for_truncation_error(error, &mut columns))
-                    .map_err(|e| Error::from(e.to_string()))?
-                {
-                    // num_batch += 1;
-                    //info!(  "Fetched batch {} with {} rows.", num_batch,  buffer.num_rows() );
-
-                    for row_index in 0..buffer.num_rows() {
-                        // let record = (0..buffer.num_cols())
-                        //     .map(|col_index| buffer.at(col_index, row_index).unwrap_or(&[]));
-                        // writer.write_record(record)?;
-
-                        let mut datas = vec![];
-
-                        for col_index in 0..buffer.num_cols() {
-                            // let col_dt = cursor.col_data_type(col_index as u16);
-                            // let is_sql_null = col_dt.unwrap() == DmDataType::;
-
-                            let col = &columns[col_index];
-
-                            let col_data = buffer.at(col_index, row_index).map(|col| col.to_vec());
-                            datas.push(DamengData {
-                                column_type: col.column_type,
-                                data: col_data,
-                                is_sql_null: false,
-                            });
-                        }
-
-                        let taos_row = DamengRow {
-                            columns: Arc::new(columns.clone()),
-                            datas: datas,
-                        };
-                        results.push(Box::new(taos_row) as Box<dyn Row>);
-                    }
-                }
-            }
-            // None => {
-            //     eprintln!("Query came back empty (not even a schema has been returned). No output has been created.");
-            // }
-            // };
+const STREAM_BUSY: &str = "connection is busy: a fetch_stream / read_lob result on it has not been read to the end. \
+     Read it to the end or drop it before making other calls on the same connection";
 
-            return Ok(results);
-        });
+/// 未读完的流，释放时连接恢复可用
+pub(crate) struct OpenStream(Arc<AtomicBool>);
+
+impl Drop for OpenStream {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Release);
+    }
+}
+
+/// 单次调用的执行参数，由 [`QueryOptions`] 和连接上的默认值合并而来
+struct Call {
+    batch_size: usize,
+    max_str_len: Option<usize>,
+    truncation: TruncationPolicy,
+    /// 语句超时（秒），`None` 表示不限制
+    timeout: Option<u32>,
+    cancel: CancelToken,
+    /// 语句已经发给服务端执行（`SQLExecute`），之后连接断开时不知道语句是否已经生效
+    executed: Cell<bool>,
+}
 
+impl Call {
+    /// 在执行语句之前调用，之后连接断开时不再重试写操作
+    fn mark_executed(&self) {
+        self.executed.set(true);
+    }
+
+    /// 连接断开后能否在新连接上重新执行：语句还没有发出，或者是只读的查询
+    fn may_retry(&self, sql: &str) -> bool {
+        !self.executed.get() || is_query(sql)
+    }
+}
+
+impl DamengConnection {
+    /// 与 `exec` 相同，但可以按语句设置超时
+    ///
+    /// 超时返回的错误可以用 `is_timeout` 判断；丢弃返回的 future 会取消正在执行的语句。
+    ///
+    /// ```no_run
+    /// # async fn demo(conn: &mut rbdc_dameng::connection::DamengConnection) -> Result<(), rbdc::Error> {
+    /// use rbdc_dameng::common::error::DamengErrorExt;
+    /// use rbdc_dameng::options::QueryOptions;
+    ///
+    /// let result = conn
+    ///     .exec_with("update biz_activity set status = 1", vec![], QueryOptions::new().timeout(10))
+    ///     .await;
+    /// if let Err(e) = &result {
+    ///     if e.is_timeout() {
+    ///         // 超过 10 秒，语句已经被服务端中止
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn exec_with(
+        &mut self,
+        sql: &str,
+        params: Vec<Value>,
+        query: QueryOptions,
+    ) -> BoxFuture<'static, Result<ExecResult, Error>> {
+        let sql = sql.to_string();
+        let call = self.call(&query);
+        let guard = CancelOnDrop(call.cancel.clone());
+        let result = self.run(move |session| {
+            if let Some(cmd) = TxCommand::parse(&sql) {
+                session.execute_tx(&call.cancel, cmd)?;
+                return Ok(ExecResult {
+                    rows_affected: 0,
+                    last_insert_id: Value::Null,
+                });
+            }
+            session.run(
+                &call.cancel,
+                || call.may_retry(&sql),
+                |session| session.exec(&sql, params.clone(), &call),
+            )
+        });
         Box::pin(async move {
-            task.await.map_err(|e| Error::from(e.to_string()))?
+            let _guard = guard;
+            result.await
         })
     }
 
-
-    fn exec(&mut self, sql: &str, params: Vec<Value>) -> BoxFuture<Result<ExecResult, Error>> {
-        let oc = self.clone();
+    /// 与 `get_rows` 相同，但可以按查询覆盖取数参数和超时
+    ///
+    /// 每次从服务端取回 `batch_size` 行，取数缓冲区只分配一次，大小为 `batch_size * 行宽`。
+    /// 丢弃返回的 future 会取消正在执行的查询。
+    pub fn get_rows_with(
+        &mut self,
+        sql: &str,
+        params: Vec<Value>,
+        query: QueryOptions,
+    ) -> BoxFuture<'static, Result<Vec<Box<dyn Row>>, Error>> {
         let sql = sql.to_string();
-        let task = tokio::task::spawn_blocking(move || {
-            let mut trans = oc.is_trans.lock()
-                .map_err(|e| Error::from(e.to_string()))?;
-
-            let binding = oc.conn.clone();
-            let conn = binding.lock().unwrap();
-
-            if sql == "begin" {
-                *trans = true;
-                let _ = conn.set_autocommit(false);
-                Ok(ExecResult { rows_affected: 0, last_insert_id: Value::Null })
-            } else if sql == "commit" {
-                // manager.aquire().await.unwrap().commit().unwrap();
-                let _ = conn.commit().map_err(|e| Error::from(e.to_string()))?;
-                let _ = conn.set_autocommit(true);
-                *trans = false;
-                Ok(ExecResult { rows_affected: 0, last_insert_id: Value::Null })
-            } else if sql == "rollback" {
-                conn.rollback().map_err(|e| Error::from(e.to_string()))?;
-
-                let _ = conn.set_autocommit(true);
-                *trans = false;
-                Ok(ExecResult { rows_affected: 0, last_insert_id: Value::Null })
-            } else {
-                 
-                let mut encoded_params: Vec<String> = vec![];
-                for x in &params {
-                    // encoded_params.push(x.encode(0)?) ;
-                    encoded_params.push(x.clone().encode(0)?);
-                    // encoded_params.push(value_to_json_string(x));
-                }
-                // log::debug!("encoded_params: {:?}",encoded_params);
-
-                // let odbc_params: Vec<Box<dyn InputParameter>> = encoded_params
-                //     .iter()
-                //     .map(|s| s.as_str().into_parameter()) // 或者 s.clone().into_parameter()
-                //     .map(|p| Box::new(p) as Box<dyn InputParameter>)
-                //     .collect();
-
-                // 创建拥有所有权的参数，而不是借用
-                let odbc_params: Vec<Box<dyn InputParameter>> = encoded_params
-                    .into_iter()  // 使用 into_iter() 而不是 iter()
-                    .map(|s| s.into_parameter())  // 直接使用 String 而不是 &str
-                    .map(|p| Box::new(p) as Box<dyn InputParameter>)
-                    .collect();
+        let call = self.call(&query);
+        let guard = CancelOnDrop(call.cancel.clone());
 
-                let mut prepared = conn.prepare(&sql)
-                    .map_err(|e| Error::from(e.to_string()))?;
-                prepared.execute(odbc_params.as_slice()).map_err(|e| Error::from(e.to_string()))?;
+        let result = self.run(move |session| {
+            let mut results = Vec::new();
+            session.fetch_batches(&sql, params, &call, |rows| {
+                results.extend(rows.into_iter().map(|row| Box::new(row) as Box<dyn Row>));
+                true
+            })?;
+            Ok(results)
+        });
 
-                // let rows_affected = prepared.row_count().unwrap().unwrap_or(0);
-                let rows_affected = match prepared.row_count() {
-                    Ok(val) => {
-                        val.unwrap_or(0)
-                    }
-                    Err(_) => {0}
-                };
+        Box::pin(async move {
+            let _guard = guard;
+            result.await
+        })
+    }
+
+    /// 读取语句返回的全部结果，适合返回多个结果集的存储过程和匿名块
+    ///
+    /// 按顺序经 `SQLMoreResults` 取回每个结果：查询部分带列信息和全部行，其它部分只有影响的行数
+    /// （见 [`ResultSet`]）。`get_rows` 只返回第一个结果。
+    ///
+    /// ```no_run
+    /// # async fn demo(conn: &mut rbdc_dameng::connection::DamengConnection) -> Result<(), rbdc::Error> {
+    /// let results = conn
+    ///     .get_results("update biz_activity set status = 1 where id = ?; select * from biz_activity", vec![rbs::Value::I32(1)])
+    ///     .await?;
+    /// assert_eq!(results[0].rows_affected, Some(1));
+    /// let rows = &results[1].rows;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn get_results(&mut self, sql: &str, params: Vec<Value>) -> BoxFuture<'static, Result<Vec<ResultSet>, Error>> {
+        self.get_results_with(sql, params, QueryOptions::default())
+    }
 
-                let mut last_insert_id = Default::default();
-                if sql.to_lowercase().starts_with("insert into") {
-                    // 获取last id
-                    // SELECT IDENT_CURRENT('PRODUCTION.PRODUCT');
-                    let table_name = sql.split_whitespace().nth(2).unwrap();
+    /// 与 [`DamengConnection::get_results`] 相同，但可以按查询覆盖取数参数和超时
+    pub fn get_results_with(
+        &mut self,
+        sql: &str,
+        params: Vec<Value>,
+        query: QueryOptions,
+    ) -> BoxFuture<'static, Result<Vec<ResultSet>, Error>> {
+        let sql = sql.to_string();
+        let call = self.call(&query);
+        let guard = CancelOnDrop(call.cancel.clone());
+        let result = self.run(move |session| session.fetch_results(&sql, params, &call));
+        Box::pin(async move {
+            let _guard = guard;
+            result.await
+        })
+    }
 
-                    last_insert_id = Self::get_last_insert_id(&conn, table_name).unwrap_or_default();
-                }
+    /// 以流的形式逐行返回查询结果，适合结果集很大、不能一次放进内存的查询
+    ///
+    /// 工作线程每次取回一批（`batch_size` 行），最多领先消费者一批，消费跟不上时取数会暂停。
+    /// 提前丢弃返回的流会取消查询并关闭游标。
+    ///
+    /// ```no_run
+    /// # async fn demo(conn: &mut rbdc_dameng::connection::DamengConnection) -> Result<(), rbdc::Error> {
+    /// use futures_util::StreamExt;
+    ///
+    /// let mut rows = conn.fetch_stream("select * from biz_activity", vec![]);
+    /// while let Some(row) = rows.next().await {
+    ///     let row = row?;
+    ///     // ...
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// 流没有读完也没有丢弃时，取数任务一直占着连接的工作线程，等待流被读取。此时在同一个连接
+    /// （包括它的克隆）上等待其它调用会死锁：该调用排在取数任务之后，取数任务又在等调用方读取。
+    /// 为避免永远挂起，这期间的其它调用（包括再开一个流）会立即返回错误。边读边写请使用另一个连接：
+    ///
+    /// ```no_run
+    /// # async fn demo(conn: &mut rbdc_dameng::connection::DamengConnection) -> Result<(), rbdc::Error> {
+    /// use futures_util::StreamExt;
+    /// use rbdc::db::Connection;
+    ///
+    /// let mut rows = conn.fetch_stream("select id from biz_activity", vec![]);
+    /// while let Some(row) = rows.next().await {
+    ///     let row = row?;
+    ///     // 错误用法：流还没读完，同一个连接上的 exec 会返回 "connection is busy" 错误
+    ///     conn.exec("update biz_activity set status = 1 where id = ?", vec![]).await?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn fetch_stream(
+        &mut self,
+        sql: &str,
+        params: Vec<Value>,
+    ) -> impl Stream<Item = Result<DamengRow, Error>> + Send + Unpin + 'static {
+        self.fetch_stream_with(sql, params, QueryOptions::default())
+    }
 
-                Ok(ExecResult { rows_affected: rows_affected as u64, last_insert_id })
+    /// 与 [`DamengConnection::fetch_stream`] 相同，但可以按查询覆盖取数参数
+    pub fn fetch_stream_with(
+        &mut self,
+        sql: &str,
+        params: Vec<Value>,
+        query: QueryOptions,
+    ) -> impl Stream<Item = Result<DamengRow, Error>> + Send + Unpin + 'static {
+        let sql = sql.to_string();
+        let call = self.call(&query);
+        let guard = CancelOnDrop(call.cancel.clone());
+
+        // 通道里只缓存一批，接收端关闭后 send 失败，取数随之停止
+        let (tx, mut rx) = mpsc::channel::<Result<Vec<DamengRow>, Error>>(1);
+        let error_tx = tx.clone();
+        let spawned = self.spawn_stream(move |session| {
+            let result = session.fetch_batches(&sql, params, &call, |rows| tx.blocking_send(Ok(rows)).is_ok());
+            if let Err(e) = result {
+                let _ = tx.blocking_send(Err(e));
             }
         });
-        Box::pin(async {
-            task.await.map_err(|e| Error::from(e.to_string()))?
+        let mut open = match spawned {
+            Ok(stream) => Some(stream),
+            Err(e) => {
+                let _ = error_tx.try_send(Err(e));
+                None
+            }
+        };
+
+        stream::poll_fn(move |cx| {
+            let _guard = &guard;
+            let batch = rx.poll_recv(cx);
+            if let Poll::Ready(None) = batch {
+                // 读完后连接可以接受其它调用
+                open.take();
+            }
+            batch
+        })
+        .flat_map(|batch| {
+            let rows: Vec<Result<DamengRow, Error>> = match batch {
+                Ok(rows) => rows.into_iter().map(Ok).collect(),
+                Err(e) => vec![Err(e)],
+            };
+            stream::iter(rows)
         })
     }
 
-    fn ping(&mut self) -> BoxFuture<Result<(), rbdc::Error>> {
-        let oc = self.clone();
-        let task = tokio::task::spawn_blocking(move || {
-            let binding = oc.conn.clone();
-            let binding = binding.lock().unwrap();
-            let x = match binding.execute("SELECT 1", (), None) {
-                Err(e) => {
-                    // if let Some(odbc_api::Error::TooLargeValueForBuffer {
-                    // rbdc::Error::from(e)
-                    Err(rbdc::Error::from(e.to_string()))
-                }
-                Ok(_) => {
-                    Ok(())
-                }
-            };
-            x
+    /// 按指定的隔离级别 / 只读模式开始事务，之后照常用 `commit` / `rollback` 结束
+    ///
+    /// 已在事务中时创建保存点，参数被忽略。丢弃返回的 future 后还没有执行的 `begin` 不再执行。
+    ///
+    /// ```no_run
+    /// # async fn demo(conn: &mut rbdc_dameng::connection::DamengConnection) -> Result<(), rbdc::Error> {
+    /// use rbdc::db::Connection;
+    /// use rbdc_dameng::options::{IsolationLevel, TxOptions};
+    ///
+    /// conn.begin_with(TxOptions::new().isolation_level(IsolationLevel::Serializable).read_only(true))
+    ///     .await?;
+    /// let rows = conn.get_rows("select count(*) from biz_activity", vec![]).await?;
+    /// conn.commit().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn begin_with(&mut self, options: TxOptions) -> BoxFuture<'static, Result<(), Error>> {
+        let cancel = CancelToken::new();
+        let guard = CancelOnDrop(cancel.clone());
+        let result = self.run(move |session| {
+            cancel.check_cancelled()?;
+            // 已在事务中时创建保存点，连接断开后不能在新连接上重新开始
+            let idle = session.tx.depth() == 0;
+            session.run(&cancel, || idle, |session| session.tx.begin(&*session.conn, &options))
         });
-        Box::pin(async {
-            task.await.map_err(|e| Error::from(e.to_string()))?
+        Box::pin(async move {
+            let _guard = guard;
+            result.await
         })
     }
 
-    fn close(&mut self) -> BoxFuture<Result<(), rbdc::Error>> {
-        let _oc = self.clone();
+    /// 按块读取查询结果第一行第一列的大字段（CLOB / TEXT / BLOB / IMAGE），适合无法整体放进内存的值
+    ///
+    /// 值为 NULL 时读取器直接结束；查询没有返回任何行时产生一个错误。提前丢弃读取器会取消查询。
+    /// 与 [`DamengConnection::fetch_stream`] 一样，读取器读完或丢弃之前，同一连接上的其它调用会返回错误。
+    ///
+    /// ```no_run
+    /// # async fn demo(conn: &mut rbdc_dameng::connection::DamengConnection) -> Result<(), Box<dyn std::error::Error>> {
+    /// use tokio_util::compat::FuturesAsyncReadCompatExt;
+    ///
+    /// let reader = conn.read_lob("select content from attachment where id = ?", vec![rbs::Value::I32(1)]);
+    /// let mut file = tokio::fs::File::create("content.bin").await?;
+    /// tokio::io::copy(&mut reader.compat(), &mut file).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn read_lob(&mut self, sql: &str, params: Vec<Value>) -> LobReader {
+        let sql = sql.to_string();
+        let call = self.call(&QueryOptions::default());
+        let guard = CancelOnDrop(call.cancel.clone());
+
+        let (tx, rx) = mpsc::channel::<Result<Vec<u8>, Error>>(1);
+        let error_tx = tx.clone();
+        let spawned = self.spawn_stream(move |session| {
+            let result = session.read_lob_chunks(&sql, params, &call, |chunk| tx.blocking_send(Ok(chunk)).is_ok());
+            if let Err(e) = result {
+                let _ = tx.blocking_send(Err(e));
+            }
+        });
+        let reader = match spawned {
+            Ok(stream) => LobReader::new(rx).hold(stream),
+            Err(e) => {
+                let _ = error_tx.try_send(Err(e));
+                LobReader::new(rx)
+            }
+        };
+        reader.cancel_on_drop(guard)
+    }
 
-        let task = tokio::task::spawn_blocking(move || {
+    /// 执行带大字段参数的语句，[`LobParam`](crate::lob::LobParam) 的内容在执行时通过 `SQLPutData` 分块发送
+    ///
+    /// ```no_run
+    /// # async fn demo(conn: &mut rbdc_dameng::connection::DamengConnection) -> Result<(), Box<dyn std::error::Error>> {
+    /// use rbdc_dameng::lob::LobParam;
+    ///
+    /// let photo = LobParam::from_path("photo.png".as_ref())?;
+    /// conn.exec_with_lobs(
+    ///     "insert into attachment (id, content) values (?, ?)",
+    ///     vec![rbs::Value::I32(1).into(), photo.into()],
+    /// )
+    /// .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn exec_with_lobs(&mut self, sql: &str, params: Vec<Param>) -> BoxFuture<'static, Result<ExecResult, Error>> {
+        let sql = sql.to_string();
+        let call = self.call(&QueryOptions::default());
+        let guard = CancelOnDrop(call.cancel.clone());
+        let result = self.run(move |session| session.exec_with_lobs(&sql, params, &call));
+        Box::pin(async move {
+            let _guard = guard;
+            result.await
+        })
+    }
 
-            // manager.aquire().await.unwrap().commit().map_err(|e| Error::from(e.to_string()))?;
-            // manager.aquire().await.unwrap().close().map_err(|e| Error::from(e.to_string()))?;
+    /// 用参数数组批量执行同一条语句，`rows` 的每个元素是一组参数
+    ///
+    /// 每次往返发送 `batch_size` 组参数（见 [`crate::batch`]），返回每一批影响的行数。
+    /// 自动提交模式下每一批单独提交，需要整体成功或失败时放在事务中执行。
+    ///
+    /// ```no_run
+    /// # async fn demo(conn: &mut rbdc_dameng::connection::DamengConnection) -> Result<(), rbdc::Error> {
+    /// use rbs::Value;
+    ///
+    /// let rows = (0..10000)
+    ///     .map(|i| vec![Value::I64(i), Value::String(format!("name {}", i))])
+    ///     .collect();
+    /// let result = conn.exec_batch("insert into biz_activity (id, name) values (?, ?)", rows).await?;
+    /// assert_eq!(result.rows_affected, 10000);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn exec_batch(&mut self, sql: &str, rows: Vec<Vec<Value>>) -> BoxFuture<'static, Result<BatchResult, Error>> {
+        self.exec_batch_with(sql, rows, QueryOptions::default())
+    }
 
-            Ok(())
-        });
-        Box::pin(async {
-            task.await.map_err(|e| Error::from(e.to_string()))?
+    /// 与 [`DamengConnection::exec_batch`] 相同，但可以覆盖每批的行数和超时（超时对每一批单独计算）
+    pub fn exec_batch_with(
+        &mut self,
+        sql: &str,
+        rows: Vec<Vec<Value>>,
+        query: QueryOptions,
+    ) -> BoxFuture<'static, Result<BatchResult, Error>> {
+        let sql = sql.to_string();
+        let call = self.call(&query);
+        let guard = CancelOnDrop(call.cancel.clone());
+        let result = self.run(move |session| session.exec_batch(&sql, &rows, &call));
+        Box::pin(async move {
+            let _guard = guard;
+            result.await
         })
     }
-}
 
-impl Drop for DamengConnection {
-    fn drop(&mut self) {
+    /// 预编译语句缓存的命中统计，见 [`crate::statement_cache`]
+    ///
+    /// 断线重连后缓存随会话重建，统计从零开始。
+    pub fn statement_cache_stats(&self) -> BoxFuture<'static, Result<CacheStats, Error>> {
+        Box::pin(self.run(|session| Ok(session.statements.stats())))
+    }
+
+    /// 启动连接的工作线程，并在线程上建立 ODBC 连接
+    pub async fn establish(opt: &DamengConnectOptions) -> Result<Self, Error> {
+        let options = Arc::new(opt.clone());
+        let name = format!("rbdc-dameng-{}", WORKER_ID.fetch_add(1, Ordering::Relaxed));
+        let worker = Worker::spawn(name, {
+            let options = options.clone();
+            move || Session::open(options)
+        })
+        .await?;
+        let sys_info = worker.run(|session| Ok(session.sys_info.clone())).await?;
+        Ok(Self {
+            worker: Arc::new(worker),
+            streaming: Arc::new(AtomicBool::new(false)),
+            options,
+            batch_size: opt.batch_size,
+            max_str_len: opt.max_str_len,
+            truncation: opt.truncation,
+            sys_info: Some(sys_info),
+        })
+    }
 
-        // println!("drop");
+    /// 在工作线程上执行操作，有未读完的流时直接返回错误，见 [`DamengConnection::fetch_stream`]
+    fn run<T, F>(&self, job: F) -> impl Future<Output = Result<T, Error>> + Send + 'static
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Session) -> Result<T, Error> + Send + 'static,
+    {
+        let result = self.check_streaming().map(|_| self.worker.run(job));
+        async move { result?.await }
+    }
 
-        if *self.is_trans.lock().unwrap() {
-            let binding = self.conn.clone();
-            let binding = binding.lock().unwrap();
-            binding.rollback().unwrap();
+    /// 启动流式取数任务，返回的 [`OpenStream`] 在流读完或丢弃时释放，之前连接上的其它调用都会返回错误
+    fn spawn_stream<F>(&self, job: F) -> Result<OpenStream, Error>
+    where
+        F: FnOnce(&mut Session) + Send + 'static,
+    {
+        if self.streaming.swap(true, Ordering::AcqRel) {
+            return Err(Error::from(STREAM_BUSY));
         }
+        let stream = OpenStream(self.streaming.clone());
+        self.worker.spawn_job(job)?;
+        Ok(stream)
     }
-}
 
-fn provide_context_for_truncation_error(error: odbc_api::Error, headline: &mut Vec<DamengColumn>) -> anyhow::Error {
-    match error {
-        odbc_api::Error::TooLargeValueForBuffer {
-            indicator: Some(required),
-            buffer_index,
-        } => {
-            let col_name = &headline[buffer_index].name;
-            anyhow!(
-                "Truncation of text or binary data in column '{col_name}' detected. Try using \
-                `--max-str-len` larger than {required}. Or do not specify it at all in order to \
-                allow for larger values. You can also use the `--ignore-truncation` flag in order \
-                to consider truncations warnings only. This will cause the truncated value to be \
-                written into the csv, and execution to be continued normally."
-            )
+    fn check_streaming(&self) -> Result<(), Error> {
+        if self.streaming.load(Ordering::Acquire) {
+            return Err(Error::from(STREAM_BUSY));
         }
-        odbc_api::Error::TooLargeValueForBuffer {
-            indicator: None,
-            buffer_index,
-        } => {
-            // let col_name = &headline[buffer_index];
-            let col_name = &headline[buffer_index].name;
-            anyhow!(
-                "Truncation of text or binary data in column '{col_name}' detected. Try using \
-                larger values of `--max-str-len` (or do not specify it at all) in order to allow \
-                for larger values. You can also use the `--ignore-truncation` flag in order to \
-                consider truncations warnings only. This will cause the truncated value to be \
-                written into the csv, and execution to be continued normally. The ODBC driver has \
-                been unable to tell how large the value that caused the truncation is."
-            )
+        Ok(())
+    }
+
+    fn call(&self, query: &QueryOptions) -> Call {
+        Call {
+            batch_size: query.fetch_size(self.batch_size),
+            max_str_len: self.max_str_len,
+            truncation: self.truncation,
+            timeout: query.timeout_secs(self.options.query_timeout),
+            cancel: CancelToken::new(),
+            executed: Cell::new(false),
         }
-        other => other.into(),
     }
 }
 
-impl DamengConnection {
-    pub async fn establish(opt: &DamengConnectOptions) -> Result<Self, Error> {
+/// 缓存的预编译语句，同时持有它借用的连接，连接总比语句后释放
+type CachedStatement = WithOwner<Prepared<StatementImpl<'static>>, OdbcApiConnection<'static>>;
 
-        // let conn = OraConnect::connect(opt.username.clone(), opt.password.clone(), opt.connect_string.clone())
-        //     .map_err(|e| Error::from(e.to_string()))?;
+impl CachedStatement {
+    fn prepare(conn: &Rc<OdbcApiConnection<'static>>, sql: &str) -> Result<Self, Error> {
+        // SAFETY: 连接的引用只用来准备语句，返回的语句保存在 WithOwner 里，不会逃到别处
+        unsafe { WithOwner::new(conn, |conn| conn.prepare(sql).map_err(odbc_error)) }
+    }
 
-        let env = &ENV;
-        let conn = env.connect_with_connection_string(&opt.connection_string, ConnectionOptions::default());
-        if conn.is_err() {
-            return Err(Error::from(conn.unwrap_err().to_string()));
+    /// 放回缓存前恢复语句属性，失败时返回 `false`，语句不再复用
+    ///
+    /// 块游标取数时修改了行数组大小，逐行取数（`SQLGetData`）要求它为 1；批量执行修改了参数集大小，
+    /// 绑定的参数数组已经随执行结束释放。
+    fn reset(&mut self) -> bool {
+        let mut handle = self.as_stmt_ref();
+        // SAFETY: 执行已经结束，语句上没有打开的游标和绑定的缓冲区
+        let results = unsafe { [handle.set_row_array_size(1), handle.set_paramset_size(1)] };
+        let results = [results[0], results[1], handle.reset_parameters()];
+        !results.iter().any(|r| matches!(r, SqlResult::Error { .. }))
+    }
+}
+
+/// 执行后的语句，依次读取它返回的结果，见 [`collect_results`]
+struct StatementResults<'a> {
+    stmt: &'a mut CachedStatement,
+    call: &'a Call,
+    block_get_data: bool,
+}
+
+impl ResultSource for StatementResults<'_> {
+    fn num_result_cols(&mut self) -> Result<usize, Error> {
+        let handle = self.stmt.as_stmt_ref();
+        let num_cols = handle.num_result_cols().into_result(&handle).map_err(odbc_error)?;
+        Ok(num_cols.max(0) as usize)
+    }
+
+    fn row_count(&mut self) -> u64 {
+        match self.stmt.as_stmt_ref().row_count() {
+            SqlResult::Success(n) | SqlResult::SuccessWithInfo(n) => n.max(0) as u64,
+            _ => 0,
         }
-        let conn = conn.unwrap();
+    }
+
+    fn read_result_set(&mut self) -> Result<(ResultSet, bool), Error> {
+        // SAFETY: num_result_cols 大于 0，当前结果是结果集，语句处于游标状态
+        let cursor = unsafe { CursorImpl::new(self.stmt.as_stmt_ref()) };
+        let mut rows = vec![];
+        let (columns, cursor) = Session::read_result_set(cursor, self.call, self.block_get_data, |batch| {
+            rows.extend(batch);
+            true
+        })?;
+        let result = ResultSet {
+            columns,
+            rows,
+            rows_affected: None,
+        };
+        // 交还语句而不关闭游标，否则后面的结果会被丢弃
+        let open = cursor.map(|cursor| cursor.into_stmt()).is_some();
+        Ok((result, open))
+    }
+
+    fn more_results(&mut self) -> Result<bool, Error> {
+        // SAFETY: 语句上是已经读完的结果集或非查询结果，more_results 消耗这个游标，不会调用 SQLCloseCursor
+        let cursor = unsafe { CursorImpl::new(self.stmt.as_stmt_ref()) };
+        Ok(cursor
+            .more_results()
+            .map_err(odbc_error)?
+            .map(|next| next.into_stmt())
+            .is_some())
+    }
+}
+
+/// 工作线程上的连接状态，只在该线程上访问
+struct Session {
+    /// 缓存的语句各自持有一份引用，连接在会话和所有语句都释放后才断开
+    conn: Rc<OdbcApiConnection<'static>>,
+    /// 按 SQL 文本缓存的语句
+    statements: StatementCache<CachedStatement>,
+    /// 事务状态和嵌套层数
+    tx: Transaction,
+    /// 表是否有自增列，键为 `'模式名.表名'`，避免每次插入后都多查一次
+    identity_tables: HashMap<String, bool>,
+    /// 驱动是否支持在块游标上调用 `SQLGetData`，见 [`Session::probe_block_get_data`]
+    block_get_data: bool,
+    sys_info: String,
+    /// 建立连接时的参数，断线重连时使用
+    options: Arc<DamengConnectOptions>,
+}
+
+impl Session {
+    /// 建立 ODBC 连接并切换到指定的模式
+    fn open(opt: Arc<DamengConnectOptions>) -> Result<Self, Error> {
+        let env = &ENV;
+        let connection_options = ConnectionOptions {
+            login_timeout_sec: opt.login_timeout,
+            ..Default::default()
+        };
+        log::debug!("connect to {}", opt);
+        let conn = env
+            .connect_with_connection_string(&opt.to_connection_string(), connection_options)
+            .map_err(|e| Error::from(format!("connect to {} failed: {}", opt, DamengError::from(e))))?;
 
-        let parsed = Self::parse_connection_string(opt.connection_string.as_str());
         // 获取特定的 Schema 值
-        let mut schema = parsed.get("schema");
-        if schema.is_none() {
-            schema = parsed.get("database");
-        }
+        let schema = opt.schema.as_deref().or(opt.get_param("database"));
 
         let sys_info = conn.database_management_system_name().unwrap_or_default();
         log::debug!("sysInfo: {}", sys_info);
 
         if let Some(database) = schema {
-            if sys_info == "DM DATABASE MANAGEMENT SYSTEM" || sys_info == "达梦数据库管理系统"
-                || sys_info.contains("DM8") || sys_info.contains("DM") {
+            if sys_info == "DM DATABASE MANAGEMENT SYSTEM"
+                || sys_info == "达梦数据库管理系统"
+                || sys_info.contains("DM8")
+                || sys_info.contains("DM")
+            {
                 let query = format!("set schema {}", database);
 
-                match conn.execute(query.as_str(), (), None) { // 执行 USE azcms; 语句
+                match conn.execute(query.as_str(), (), None) {
+                    // 执行 USE azcms; 语句
                     Ok(_) => log::debug!("set schema {} 成功", database),
                     Err(e) => log::debug!("set schema {} 失败: {}", database, e),
                 }
             } else {
                 let query = format!("USE {}", database);
 
-                match conn.execute(query.as_str(), (), None) { // 执行 USE azcms; 语句
+                match conn.execute(query.as_str(), (), None) {
+                    // 执行 USE azcms; 语句
                     Ok(_) => log::debug!("USE {} 成功", database),
                     Err(e) => log::debug!("USE {} 失败: {}", database, e),
                 }
             }
         }
-
+        let block_get_data = Self::block_get_data(&conn, &opt);
         Ok(Self {
-            // conn_manager: ODBCConnectionManager::new(&opt.connection_string, 5),
-            conn: Arc::new(Mutex::new(conn)),
-            is_trans: Arc::new(Mutex::new(false)),
-            batch_size: opt.batch_size,
-            max_str_len: opt.max_str_len,
-            sys_info: Some(sys_info),
+            conn: Rc::new(conn),
+            statements: StatementCache::new(opt.statement_cache_capacity),
+            tx: Transaction::new(opt.tx_options()),
+            identity_tables: HashMap::new(),
+            block_get_data,
+            sys_info,
+            options: opt,
         })
     }
 
-    fn parse_connection_string(conn_str: &str) -> HashMap<String, String> {
-        let mut kv_pairs = HashMap::new();
+    /// 驱动是否支持在块游标上调用 `SQLGetData`，同一个驱动（没有驱动名时按 DSN）在进程里只探测一次
+    ///
+    /// 连接池扩容和断线重连时不再多一次往返；探测时连接断开的结果不缓存，下次建立连接时重新探测。
+    fn block_get_data(conn: &OdbcApiConnection<'static>, opt: &DamengConnectOptions) -> bool {
+        let key = opt
+            .driver
+            .as_deref()
+            .or(opt.get_param("DSN"))
+            .unwrap_or_default()
+            .to_string();
+        if let Some(supported) = BLOCK_GET_DATA.lock().unwrap_or_else(PoisonError::into_inner).get(&key) {
+            return *supported;
+        }
+        let supported = match Self::probe_block_get_data(conn) {
+            Ok(supported) => supported,
+            Err(e) => {
+                let e = odbc_error(e);
+                log::debug!("SQLGetData on block cursors not supported: {}", e);
+                // 连接断开时还不知道驱动是否支持，不缓存
+                if e.is_connection_broken() {
+                    return false;
+                }
+                false
+            }
+        };
+        BLOCK_GET_DATA
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(key, supported);
+        supported
+    }
 
-        // 将连接字符串分割为键值对
-        let pairs: Vec<&str> = conn_str.split(';').collect();
+    /// 驱动是否支持在块游标中用 `SQLSetPos` 定位到某一行后调用 `SQLGetData`（`SQL_GD_BLOCK`）
+    ///
+    /// odbc-api 不暴露连接句柄，无法直接查询 `SQL_GETDATA_EXTENSIONS`，这里用一条小查询实际试一次：
+    /// 第一列绑定、第二列用 `SQLGetData` 读取。失败时含有长列的结果集整体逐行读取。
+    fn probe_block_get_data(conn: &OdbcApiConnection<'static>) -> Result<bool, odbc_api::Error> {
+        let Some(mut cursor) = conn.execute("SELECT 1, 'x' FROM DUAL", (), None)? else {
+            return Ok(false);
+        };
+        let handle = cursor.as_stmt_ref().as_sys();
+        let buffer =
+            ColumnarAnyBuffer::from_descs_and_indices(2, [(1, BufferDesc::I32 { nullable: true })].into_iter());
+        let mut block = cursor.bind_buffer(buffer)?;
+        if block.fetch()?.is_none() {
+            return Ok(false);
+        }
+        // SAFETY: 刚取回的这一块至少有一行，第二列没有绑定
+        let mut row = unsafe { BlockRow::position(handle, 0)? };
+        Ok(get_long(&mut row, 2, false)?.is_some())
+    }
 
-        for pair in pairs {
-            // 将每个键值对按 '=' 分割
-            let parts: Vec<&str> = pair.split('=').collect();
-            if parts.len() == 2 {
-                let key = parts[0].trim().to_string().to_lowercase();
-                let value = parts[1].trim().to_string();
-                kv_pairs.insert(key, value);
+    /// 在连接上执行操作，连接断开且不在事务中时按重连策略重新连接后再执行一次，见 [`crate::reconnect`]
+    ///
+    /// `may_retry` 返回 `false` 时不重试，用于已经发给服务端的写操作（见 [`Call::may_retry`]）和
+    /// 已经向调用方交出部分结果的查询；调用被取消后也不再重试，返回取消错误。
+    ///
+    /// 重连时整个会话被替换；只有不在事务中时才会重连，事务状态不会丢失。
+    fn run<T, R, O>(&mut self, cancel: &CancelToken, may_retry: R, op: O) -> Result<T, Error>
+    where
+        R: Fn() -> bool,
+        O: FnMut(&mut Session) -> Result<T, Error>,
+    {
+        let options = self.options.clone();
+        with_reconnect(
+            self,
+            &options.reconnect,
+            |session| session.tx.depth() == 0 && !cancel.is_cancelled() && may_retry(),
+            || Self::open(options.clone()),
+            op,
+        )
+        .map_err(|e| cancel.check(e))
+    }
+
+    /// 执行事务控制语句
+    ///
+    /// 只有事务开始之前的调用可以重连后重试：事务中连接断开时服务端已经回滚了事务，
+    /// 在新连接上提交或创建保存点都会让调用方误以为事务还在。
+    ///
+    /// 事务控制语句不登记语句句柄，调用方丢弃 future 后排队的操作照样会执行，这里先检查取消标记，
+    /// 避免连接留在没有人知道的事务里。
+    fn execute_tx(&mut self, cancel: &CancelToken, cmd: TxCommand) -> Result<(), Error> {
+        cancel.check_cancelled()?;
+        let idle = self.tx.depth() == 0;
+        self.run(cancel, || idle, |session| session.tx.execute(&*session.conn, cmd))
+    }
+
+    fn exec(&mut self, sql: &str, params: Vec<Value>, call: &Call) -> Result<ExecResult, Error> {
+        let target = parse_insert(sql);
+        // 带 RETURNING 子句时，返回结果的第一个值就是插入的 id
+        let returning = target.as_ref().is_some_and(|t| t.returning);
+        if let Some(target) = target.filter(|t| !t.returning) {
+            if self.has_identity(&target) {
+                return self.exec_insert_identity(sql, params, call);
+            }
+        }
+        self.with_statement(sql, call, |_, prepared| {
+            let odbc_params = Self::bind_params(prepared, params)?;
+            call.mark_executed();
+            let last_insert_id = match prepared.execute(odbc_params.as_slice()).map_err(odbc_error)? {
+                Some(cursor) if returning => Self::first_value(cursor, call.max_str_len)?,
+                _ => Value::Null,
+            };
+            Ok(ExecResult {
+                rows_affected: Self::rows_affected(prepared),
+                last_insert_id,
+            })
+        })
+    }
+
+    /// 插入有自增列的表，在同一批语句中取 `SCOPE_IDENTITY()`，见 [`with_scope_identity`]
+    ///
+    /// 取不到时（如经触发器插入）返回 `Value::Null`，不退回到 `IDENT_CURRENT`，它可能返回其它会话插入的值。
+    fn exec_insert_identity(&mut self, sql: &str, params: Vec<Value>, call: &Call) -> Result<ExecResult, Error> {
+        let sql = with_scope_identity(sql);
+        let results = self.with_statement(&sql, call, |session, stmt| {
+            Self::fetch_results_prepared(stmt, params, call, session.block_get_data)
+        })?;
+        let rows_affected = results.first().and_then(|r| r.rows_affected).unwrap_or(0);
+        let last_insert_id = results
+            .into_iter()
+            .find(|r| r.is_query())
+            .and_then(|r| r.rows.into_iter().next())
+            .filter(|_| rows_affected > 0)
+            .and_then(|mut row| row.get(0).ok())
+            .map_or(Value::Null, identity_value);
+        Ok(ExecResult {
+            rows_affected,
+            last_insert_id,
+        })
+    }
+
+    /// 大字段参数只能读取一次，交给语句之后连接断开不再重试
+    fn exec_with_lobs(&mut self, sql: &str, params: Vec<Param>, call: &Call) -> Result<ExecResult, Error> {
+        log::debug!("exec_with_lobs执行的sql:{}", sql);
+        let params = Cell::new(Some(params));
+        self.run(
+            &call.cancel,
+            || call.may_retry(sql),
+            |session| {
+                session.with_statement(sql, call, |_, prepared| {
+                    call.mark_executed();
+                    let params = params.take().expect("parameters are taken only once");
+                    Self::exec_prepared_with_lobs(prepared, params, call)
+                })
+            },
+        )
+    }
+
+    fn exec_prepared_with_lobs(
+        prepared: &mut CachedStatement,
+        params: Vec<Param>,
+        call: &Call,
+    ) -> Result<ExecResult, Error> {
+        let mut value_numbers = vec![];
+        let mut values = vec![];
+        let mut lobs = vec![];
+        for (idx, param) in params.into_iter().enumerate() {
+            let n = idx as u16 + 1;
+            match param {
+                Param::Value(v) => {
+                    value_numbers.push(n);
+                    values.push(v);
+                }
+                Param::Lob(lob) => lobs.push((n, lob)),
+            }
+        }
+        let values = encode_params_with(values, |i| {
+            match prepared.describe_param(value_numbers[i as usize - 1]) {
+                Ok(desc) => Some(desc.data_type),
+                Err(e) => {
+                    log::debug!("SQLDescribeParam not supported, bind NULL as VARCHAR: {}", e);
+                    None
+                }
             }
+        })?;
+        let mut params = MixedParams {
+            values: value_numbers.into_iter().zip(values).collect(),
+            lobs: lobs.iter_mut().map(|(n, lob)| (*n, BlobParam::new(lob))).collect(),
+        };
+        prepared
+            .execute(&mut params)
+            .map_err(|e| call.cancel.check(odbc_error(e)))?;
+
+        Ok(ExecResult {
+            rows_affected: Self::rows_affected(prepared),
+            last_insert_id: Value::Null,
+        })
+    }
+
+    /// 语句影响的行数，驱动不支持 `SQLRowCount` 或没有行数时为 0
+    fn rows_affected(stmt: &mut CachedStatement) -> u64 {
+        stmt.row_count().ok().flatten().unwrap_or(0) as u64
+    }
+
+    /// 批量执行，只有准备语句时连接断开才重连后重试
+    fn exec_batch(&mut self, sql: &str, rows: &[Vec<Value>], call: &Call) -> Result<BatchResult, Error> {
+        log::debug!("exec_batch执行的sql:{}, {} rows", sql, rows.len());
+        let mut result = BatchResult::default();
+        self.run(
+            &call.cancel,
+            || call.may_retry(sql),
+            |session| {
+                session.with_statement(sql, call, |_, stmt| {
+                    call.mark_executed();
+                    batch::execute(stmt, rows, call.batch_size, |rows_affected| {
+                        result.rows_affected += rows_affected;
+                        result.batches.push(rows_affected);
+                    })
+                })
+            },
+        )?;
+        Ok(result)
+    }
+
+    /// 执行查询并逐批取回结果
+    ///
+    /// `on_batch` 返回 `false` 时停止取数，游标随之关闭。
+    fn fetch_batches<F>(&mut self, sql: &str, params: Vec<Value>, call: &Call, mut on_batch: F) -> Result<(), Error>
+    where
+        F: FnMut(Vec<DamengRow>) -> bool,
+    {
+        // 事务控制语句没有结果集
+        if let Some(cmd) = TxCommand::parse(sql) {
+            return self.execute_tx(&call.cancel, cmd);
+        }
+        // 已经交出部分结果后不能再重试
+        let delivered = Cell::new(false);
+        let mut on_batch = |rows| {
+            delivered.set(true);
+            on_batch(rows)
+        };
+        self.run(
+            &call.cancel,
+            || !delivered.get() && call.may_retry(sql),
+            |session| session.fetch(sql, params.clone(), call, &mut on_batch),
+        )
+    }
+
+    fn fetch<F>(&mut self, sql: &str, params: Vec<Value>, call: &Call, on_batch: F) -> Result<(), Error>
+    where
+        F: FnMut(Vec<DamengRow>) -> bool,
+    {
+        // 执行查询
+        log::debug!("get_rows执行的sql:{}", sql);
+        self.with_statement(sql, call, |session, stmt| {
+            Self::fetch_prepared(stmt, params, call, session.block_get_data, on_batch)
+        })
+    }
+
+    /// 只读取第一个结果，后面的结果随游标关闭一起丢弃
+    fn fetch_prepared<F>(
+        stmt: &mut CachedStatement,
+        params: Vec<Value>,
+        call: &Call,
+        block_get_data: bool,
+        on_batch: F,
+    ) -> Result<(), Error>
+    where
+        F: FnMut(Vec<DamengRow>) -> bool,
+    {
+        let odbc_params = Self::bind_params(stmt, params)?;
+        call.mark_executed();
+        if let Some(cursor) = stmt.execute(odbc_params.as_slice()).map_err(odbc_error)? {
+            Self::read_result_set(cursor, call, block_get_data, on_batch)?;
+        }
+        Ok(())
+    }
+
+    /// 执行语句并依次读取全部结果（`SQLMoreResults`），见 [`DamengConnection::get_results`]
+    fn fetch_results(&mut self, sql: &str, params: Vec<Value>, call: &Call) -> Result<Vec<ResultSet>, Error> {
+        log::debug!("get_results执行的sql:{}", sql);
+        if let Some(cmd) = TxCommand::parse(sql) {
+            self.execute_tx(&call.cancel, cmd)?;
+            return Ok(vec![ResultSet::affected(0)]);
         }
+        // 可能是带多个结果的存储过程调用，执行后只有查询可以重试
+        self.run(
+            &call.cancel,
+            || call.may_retry(sql),
+            |session| {
+                session.with_statement(sql, call, |session, stmt| {
+                    Self::fetch_results_prepared(stmt, params.clone(), call, session.block_get_data)
+                })
+            },
+        )
+    }
 
-        kv_pairs // 返回键值对集合
+    fn fetch_results_prepared(
+        stmt: &mut CachedStatement,
+        params: Vec<Value>,
+        call: &Call,
+        block_get_data: bool,
+    ) -> Result<Vec<ResultSet>, Error> {
+        let odbc_params = Self::bind_params(stmt, params)?;
+        call.mark_executed();
+        // 不关闭游标，否则后面的结果会被丢弃
+        if let Some(cursor) = stmt.execute(odbc_params.as_slice()).map_err(odbc_error)? {
+            cursor.into_stmt();
+        }
+        collect_results(&mut StatementResults {
+            stmt,
+            call,
+            block_get_data,
+        })
     }
 
-    fn get_last_insert_id(conn: &MutexGuard<OdbcApiConnection<'static>>, table_name: &str) -> Result<Value, rbdc::Error> {
-        // 达梦数据库获取最后插入ID的SQL语句
-        // let sql = format!("SELECT IDENT_CURRENT('') as last_id", table_name);
-        let sql = format!("SELECT max(id) as last_id FROM {} ", table_name);
-        
-        let x = match conn.execute(sql.as_str(), (), None) {
+    /// 读取游标上当前的结果集，读完后交还游标以便继续读取后面的结果；`on_batch` 要求停止时游标关闭，返回 `None`
+    ///
+    /// `block_get_data` 为驱动是否支持在块游标上调用 `SQLGetData`，见 [`FetchPlan`]。
+    fn read_result_set<C, F>(
+        mut cursor: C,
+        call: &Call,
+        block_get_data: bool,
+        mut on_batch: F,
+    ) -> Result<(Arc<Vec<DamengColumn>>, Option<C>), Error>
+    where
+        C: Cursor,
+        F: FnMut(Vec<DamengRow>) -> bool,
+    {
+        let nz_max_str_len = call
+            .max_str_len
+            .and_then(NonZeroUsize::new)
+            .unwrap_or(NonZeroUsize::new(DEFAULT_MAX_STR_LEN).unwrap());
+
+        let batch_size = call.batch_size;
+
+        let mut columns: Vec<DamengColumn> = vec![];
+
+        let mut descs: Vec<BufferDesc> = vec![];
+
+        let mut column_description = Default::default();
+
+        for index in 1..=cursor.num_result_cols().unwrap_or(0) {
+            cursor
+                .describe_col(index as u16, &mut column_description)
+                .map_err(odbc_error)?;
+
+            let nullable = matches!(
+                column_description.nullability,
+                Nullability::Unknown | Nullability::Nullable
+            );
+            descs.push(column_buffer_desc(
+                column_description.data_type,
+                nullable,
+                nz_max_str_len,
+            ));
+
+            columns.push(DamengColumn {
+                name: column_description
+                    .name_to_string()
+                    .unwrap_or("".to_string())
+                    .to_lowercase(),
+                column_type: column_description.data_type,
+                nullability: nullable,
+            });
+        }
+
+        // 所有行共享同一份列信息
+        let row_columns = Arc::new(columns.clone());
+
+        // 大字段不论策略如何都读取完整的值
+        let types: Vec<_> = columns.iter().map(|col| col.column_type).collect();
+        let plan = FetchPlan::new(&types, call.truncation, nz_max_str_len, block_get_data);
+        if plan.is_row_by_row() {
+            log::debug!(
+                "some columns are LOBs or exceed max_str_len {}, fetch row by row with SQLGetData",
+                nz_max_str_len
+            );
+            let finished =
+                Self::fetch_rows_with_get_data(&mut cursor, row_columns.clone(), descs, batch_size, on_batch)?;
+            return Ok((row_columns, finished.then_some(cursor)));
+        }
+
+        // 只绑定普通列，长列在每一块取回后逐行读取
+        let bound_columns: Vec<DamengColumn> = plan.bound.iter().map(|&i| columns[i].clone()).collect();
+        let bound_descs: Vec<BufferDesc> = plan.bound.iter().map(|&i| descs[i]).collect();
+        if !plan.get_data.is_empty() {
+            log::debug!(
+                "columns {:?} are LOBs or exceed max_str_len {}, read them with SQLGetData after each block",
+                plan.get_data.iter().map(|&i| &columns[i].name).collect::<Vec<_>>(),
+                nz_max_str_len
+            );
+        }
+        log::debug!(
+            "fetch {} rows per batch, buffer size {} bytes",
+            batch_size,
+            batch_size.saturating_mul(row_width(&bound_descs))
+        );
+        let buffers = plan
+            .bound
+            .iter()
+            .map(|&i| {
+                AnyBuffer::try_from_desc(batch_size, descs[i])
+                    .map(|buffer| (i as u16 + 1, buffer))
+                    .map_err(|e| odbc_error(e.add_context(i as u16)))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        let mut buffer = ColumnarAnyBuffer::new(buffers);
+
+        let handle = cursor.as_stmt_ref().as_sys();
+        let mut row_set_cursor = match cursor.bind_buffer(&mut buffer) {
+            Ok(block_cursor) => block_cursor,
             Err(e) => {
-                Err(rbdc::Error::from(e.to_string()))
+                return Err(Error::from(format!(
+                    "bind fetch buffer failed: {}",
+                    DamengError::from(e)
+                )))
             }
-            Ok(Some(mut cursor)) => {
-                // 获取查询结果
-                if let Some(mut row) = cursor.next_row().map_err(|e| rbdc::Error::from(e.to_string()))? {
-                    // 获取第一列的值（last_id）
-                    let mut field = Nullable::<i64>::null();
-                    row.get_data(1, &mut field).map_err(|e| rbdc::Error::from(e.to_string()))?;
-
-                    if let Some(value) = field.as_opt() {
-                        // println!("Value: {}", value);
-                        Ok(Value::I64(*value))
-
-                    } else {
-                        Ok(Value::Null)
+        };
+
+        let check_truncation = call.truncation != TruncationPolicy::WarnAndTruncate;
+        let mut warned = vec![false; bound_columns.len()];
+        while let Some(buffer) = row_set_cursor
+            .fetch_with_truncation_check(check_truncation)
+            .map_err(|error| truncation_error(error, &bound_columns, nz_max_str_len))?
+        {
+            if !check_truncation {
+                for (col_index, col) in bound_columns.iter().enumerate() {
+                    if let (false, Some(len)) = (warned[col_index], truncated_len(buffer.column(col_index))) {
+                        warned[col_index] = true;
+                        log::warn!(
+                            "value of column '{}' truncated to max_str_len {} ({:?})",
+                            col.name,
+                            nz_max_str_len,
+                            len
+                        );
                     }
-
-                } else {
-                    Ok(Value::Null)
                 }
             }
-            _ => {
-                Ok(Value::Null)
+            let mut rows = Vec::with_capacity(buffer.num_rows());
+            for row_index in 0..buffer.num_rows() {
+                let mut datas: Vec<DamengData> = plan
+                    .bound
+                    .iter()
+                    .enumerate()
+                    .map(|(buffer_index, &col_index)| {
+                        DamengData::from_cell(
+                            columns[col_index].column_type,
+                            cell_at(buffer.column(buffer_index), row_index),
+                        )
+                    })
+                    .collect();
+                if !plan.get_data.is_empty() {
+                    // SAFETY: handle 是块游标所在的语句，刚取回的这一块有 num_rows 行；
+                    // BlockRow 只在这一块的范围内使用，读取的都是未绑定的列
+                    let mut row = unsafe { BlockRow::position(handle, row_index) }.map_err(odbc_error)?;
+                    for &col_index in &plan.get_data {
+                        let col = &columns[col_index];
+                        let cell = get_cell(&mut row, col_index as u16 + 1, descs[col_index]).map_err(|e| {
+                            Error::from(format!(
+                                "get value of column '{}' failed: {}",
+                                col.name,
+                                DamengError::from(e)
+                            ))
+                        })?;
+                        datas.push(DamengData::from_cell(col.column_type, cell));
+                    }
+                }
+                rows.push(DamengRow {
+                    columns: row_columns.clone(),
+                    datas,
+                });
+            }
+            if !on_batch(rows) {
+                log::debug!("fetch cancelled, close cursor");
+                return Ok((row_columns, None));
+            }
+        }
+        let (cursor, _) = row_set_cursor.unbind().map_err(odbc_error)?;
+        Ok((row_columns, Some(cursor)))
+    }
+
+    /// 不绑定缓冲区，逐行用 `SQLGetData` 取值，文本 / 二进制值不受 `max_str_len` 限制
+    ///
+    /// 读完全部行时返回 `true`，`on_batch` 要求停止时返回 `false`。
+    fn fetch_rows_with_get_data<C, F>(
+        cursor: &mut C,
+        row_columns: Arc<Vec<DamengColumn>>,
+        descs: Vec<BufferDesc>,
+        batch_size: usize,
+        mut on_batch: F,
+    ) -> Result<bool, Error>
+    where
+        C: Cursor,
+        F: FnMut(Vec<DamengRow>) -> bool,
+    {
+        let mut rows = Vec::with_capacity(batch_size);
+        while let Some(mut row) = cursor.next_row().map_err(odbc_error)? {
+            let mut datas = Vec::with_capacity(descs.len());
+            for (col_index, (col, desc)) in row_columns.iter().zip(&descs).enumerate() {
+                let cell = get_cell(&mut row, col_index as u16 + 1, *desc).map_err(|e| {
+                    Error::from(format!(
+                        "get value of column '{}' failed: {}",
+                        col.name,
+                        DamengError::from(e)
+                    ))
+                })?;
+                datas.push(DamengData::from_cell(col.column_type, cell));
+            }
+            rows.push(DamengRow {
+                columns: row_columns.clone(),
+                datas,
+            });
+            if rows.len() >= batch_size && !on_batch(std::mem::take(&mut rows)) {
+                log::debug!("fetch cancelled, close cursor");
+                return Ok(false);
             }
-        }; x
+        }
+        if !rows.is_empty() {
+            return Ok(on_batch(rows));
+        }
+        Ok(true)
+    }
+
+    fn read_lob_chunks<F>(&mut self, sql: &str, params: Vec<Value>, call: &Call, mut on_chunk: F) -> Result<(), Error>
+    where
+        F: FnMut(Vec<u8>) -> bool,
+    {
+        // 已经交出部分内容后不能再重试
+        let delivered = Cell::new(false);
+        let mut on_chunk = |chunk| {
+            delivered.set(true);
+            on_chunk(chunk)
+        };
+        self.run(
+            &call.cancel,
+            || !delivered.get() && call.may_retry(sql),
+            |session| session.read_lob(sql, params.clone(), call, &mut on_chunk),
+        )
+    }
+
+    fn read_lob<F>(&mut self, sql: &str, params: Vec<Value>, call: &Call, on_chunk: F) -> Result<(), Error>
+    where
+        F: FnMut(Vec<u8>) -> bool,
+    {
+        log::debug!("read_lob执行的sql:{}", sql);
+
+        self.with_statement(sql, call, |_, stmt| {
+            let odbc_params = Self::bind_params(stmt, params)?;
+            call.mark_executed();
+            let mut cursor = stmt
+                .execute(odbc_params.as_slice())
+                .map_err(odbc_error)?
+                .ok_or_else(|| Error::from("read_lob: statement did not return a result set"))?;
+
+            let binary = is_binary(cursor.col_data_type(1).map_err(odbc_error)?);
+            let mut row = cursor
+                .next_row()
+                .map_err(odbc_error)?
+                .ok_or_else(|| Error::from("read_lob: query returned no rows"))?;
+            read_chunks(&mut row, 1, binary, LOB_CHUNK_SIZE, on_chunk)
+                .map(|_| ())
+                .map_err(odbc_error)
+        })
     }
 
+    /// 用 SQL 对应的预编译语句执行 `op`，执行期间登记语句以便取消
+    ///
+    /// 语句优先从缓存中取，执行成功后放回；出错的语句直接释放。执行 DDL 或报告表结构变化的错误时
+    /// 清空缓存，之后的语句重新准备。
+    fn with_statement<T, O>(&mut self, sql: &str, call: &Call, op: O) -> Result<T, Error>
+    where
+        O: FnOnce(&mut Session, &mut CachedStatement) -> Result<T, Error>,
+    {
+        self.tx.ensure_autocommit(&*self.conn)?;
+        let mut stmt = self.prepare(sql, call.timeout)?;
+        let result = call
+            .cancel
+            .register(&mut *stmt)
+            .and_then(|_registration| op(self, &mut stmt));
+        self.release(sql, stmt, &result);
+        result
+    }
+
+    /// 从缓存中取出语句，没有时重新准备，并设置本次调用的超时
+    fn prepare(&mut self, sql: &str, timeout: Option<u32>) -> Result<CachedStatement, Error> {
+        let mut stmt = match self.statements.take(sql) {
+            Some(stmt) => stmt,
+            None => CachedStatement::prepare(&self.conn, sql)?,
+        };
+        // 缓存的语句上可能还留着上一次调用的超时，0 表示不限制
+        stmt.set_query_timeout_sec(timeout.unwrap_or(0) as usize)
+            .map_err(odbc_error)?;
+        Ok(stmt)
+    }
+
+    /// 执行结束后放回语句，或按结果使缓存失效
+    fn release<T>(&mut self, sql: &str, mut stmt: CachedStatement, result: &Result<T, Error>) {
+        if changes_schema(sql) {
+            log::debug!("schema changed by '{}', clear statement cache", sql);
+            self.statements.clear();
+            // 表可能被删除重建或增删了自增列
+            self.identity_tables.clear();
+            return;
+        }
+        match result {
+            Ok(_) if stmt.reset() => self.statements.put(sql, stmt),
+            Ok(_) => log::debug!("reset statement attributes failed, drop statement: {}", sql),
+            Err(e) if e.is_schema_changed() => {
+                log::debug!("statement failed with schema change, clear statement cache: {}", e);
+                self.statements.clear();
+            }
+            Err(_) => {}
+        }
+    }
+
+    /// 编码参数，`NULL` 参数通过 `SQLDescribeParam` 推断类型
+    fn bind_params<S: AsStatementRef>(
+        stmt: &mut Prepared<S>,
+        params: Vec<Value>,
+    ) -> Result<Vec<Box<dyn InputParameter>>, Error> {
+        encode_params_with(params, |n| match stmt.describe_param(n) {
+            Ok(desc) => Some(desc.data_type),
+            Err(e) => {
+                log::debug!("SQLDescribeParam not supported, bind NULL as VARCHAR: {}", e);
+                None
+            }
+        })
+    }
+
+    /// 读取结果集第一行第一列的值，没有行时返回 `Value::Null`
+    fn first_value(mut cursor: impl Cursor, max_str_len: Option<usize>) -> Result<Value, Error> {
+        let max_str_len = max_str_len
+            .and_then(NonZeroUsize::new)
+            .unwrap_or(NonZeroUsize::new(DEFAULT_MAX_STR_LEN).unwrap());
+        let column_type = cursor.col_data_type(1).map_err(odbc_error)?;
+        let desc = column_buffer_desc(column_type, true, max_str_len);
+        let Some(mut row) = cursor.next_row().map_err(odbc_error)? else {
+            return Ok(Value::Null);
+        };
+        let cell = get_cell(&mut row, 1, desc).map_err(odbc_error)?;
+        Value::decode(&DamengData::from_cell(column_type, cell))
+    }
+
+    /// 目标表是否有自增列，结果按表缓存，避免每次插入都多查一次；查询失败时视为没有
+    fn has_identity(&mut self, target: &InsertTarget) -> bool {
+        let table = quote_literal(&target.qualified_name());
+        if let Some(has_identity) = self.identity_tables.get(&table) {
+            return *has_identity;
+        }
+        // 没有自增列时 IDENT_SEED 返回 NULL
+        let sql = format!("SELECT IDENT_SEED({})", table);
+        match Self::query_i64(&self.conn, &sql) {
+            Ok(seed) => {
+                self.identity_tables.insert(table, seed.is_some());
+                seed.is_some()
+            }
+            Err(e) => {
+                log::debug!("check identity column of {} failed: {}", table, e);
+                false
+            }
+        }
+    }
+
+    fn query_i64(conn: &OdbcApiConnection<'static>, sql: &str) -> Result<Option<i64>, Error> {
+        let Some(mut cursor) = conn.execute(sql, (), None).map_err(odbc_error)? else {
+            return Ok(None);
+        };
+        let Some(mut row) = cursor.next_row().map_err(odbc_error)? else {
+            return Ok(None);
+        };
+        let mut field = Nullable::<i64>::null();
+        row.get_data(1, &mut field).map_err(odbc_error)?;
+        Ok(field.into_opt())
+    }
+}
+
+/// 所有连接句柄释放后工作线程退出，未结束的事务在这里回滚；回滚失败只记录日志，不会 panic
+impl Drop for Session {
+    fn drop(&mut self) {
+        if self.tx.depth() == 0 {
+            return;
+        }
+        match self.tx.rollback_all(&*self.conn) {
+            Ok(()) => log::debug!("connection dropped inside a transaction, rolled back"),
+            Err(e) => log::warn!("connection dropped inside a transaction, rollback failed: {}", e),
+        }
+    }
 }
@@ -1,7 +1,8 @@
 use std::str::FromStr;
 
+use crate::buffer::is_binary;
 use crate::common::data_type::DmDataType;
-use crate::DamengData;
+use crate::{CellValue, DamengData};
 use bigdecimal::BigDecimal;
 use odbc_api::sys::SqlDataType;
 use rbdc::{datetime::DateTime, Error};
@@ -14,228 +15,123 @@ pub trait Decode {
 /// 将数据库返回的数据转换为 Rbatis 的Value 类型
 impl Decode for Value {
     fn decode(row: &DamengData) -> Result<Value, Error> {
-        if row.is_sql_null {
-            return Ok(Value::Null);
-        }
-
-        if row.data.as_ref().is_none() {
-            return Ok(Value::Null);
-        }
-
-        let value;
+        // NULL 以取数缓冲区的指示器为准，与空字符串 / 空二进制区分开
+        let data = match &row.data {
+            Some(data) if !row.is_sql_null => data,
+            _ => return Ok(Value::Null),
+        };
 
-        match &row.data {
-            Some(data) => {
-                value = String::from_utf8(data.to_vec()).unwrap_or_default();
-                // // 处理空数组的情况
-                // if value.trim() == "[]" {
-                //     return Ok(Value::Array(vec![]));
-                // }
-            }
-            None => {
-                value = "".to_string();
+        match data {
+            CellValue::Bytes(bytes) => decode_bytes(row.column_type, bytes),
+            CellValue::I16(v) => Ok(Value::I32(*v as i32)),
+            CellValue::I32(v) => Ok(Value::I32(*v)),
+            CellValue::I64(v) => Ok(Value::I64(*v)),
+            CellValue::F32(v) => Ok(Value::F32(*v)),
+            CellValue::F64(v) => Ok(Value::F64(*v)),
+            CellValue::Date(d) => {
+                let date = fastdate::Date {
+                    day: d.day as u8,
+                    mon: d.month as u8,
+                    year: d.year as i32,
+                };
+                let dt = fastdate::DateTime::from((date, fastdate::offset_sec()));
+                Ok(Value::Ext("Date", Box::new(Value::I64(dt.unix_timestamp_millis()))))
+            }
+            CellValue::Time(t) => Ok(Value::Ext(
+                "Time",
+                Box::new(Value::String(format!("{:02}:{:02}:{:02}", t.hour, t.minute, t.second))),
+            )),
+            CellValue::Timestamp(ts) => {
+                let date = fastdate::Date {
+                    day: ts.day as u8,
+                    mon: ts.month as u8,
+                    year: ts.year as i32,
+                };
+                let time = fastdate::Time {
+                    nano: ts.fraction,
+                    sec: ts.second as u8,
+                    minute: ts.minute as u8,
+                    hour: ts.hour as u8,
+                };
+                // 与文本格式一样按本地时区解释
+                let dt = fastdate::DateTime::from((date, time, fastdate::offset_sec()));
+                Ok(Value::Ext("Timestamp", Box::new(Value::I64(dt.unix_timestamp_millis()))))
             }
         }
+    }
+}
 
-        match row.column_type {
-            DmDataType::Numeric { precision: p, scale: s } => {
-                // let value = row.data.as_ref().unwrap().clone();
-                if p == 0 && s == -127 {
-                    // it means number(*)
-                    let dec =
-                        BigDecimal::from_str(&value).map_err(|e| Error::from(e.to_string()))?;
-                    if dec.is_integer() {
-                        let d = dec.digits();
-                        if 1 <= d && d <= 9 {
-                            let a = value.parse::<i32>()?;
-                            return Ok(Value::I32(a));
-                        } else if 10 <= d && d <= 18 {
-                            let a = value.parse::<i64>()?;
-                            return Ok(Value::I64(a));
-                        }
-                        return Ok(Value::String(dec.to_string()).into_ext("Decimal"));
+/// 解码驱动以文本 / 二进制返回的值
+fn decode_bytes(column_type: DmDataType, data: &[u8]) -> Result<Value, Error> {
+    // BLOB / IMAGE 等二进制原样返回，不能按 UTF-8 转换
+    if is_binary(column_type) {
+        return Ok(Value::Binary(data.to_vec()));
+    }
+    // 截断的文本可能切在多字节字符中间
+    let value = String::from_utf8_lossy(data).into_owned();
+
+    match column_type {
+        DmDataType::Numeric { precision: p, scale: s } => {
+            if p == 0 && s == -127 {
+                // it means number(*)
+                let dec = BigDecimal::from_str(&value).map_err(|e| Error::from(e.to_string()))?;
+                if dec.is_integer() {
+                    let d = dec.digits();
+                    if (1..=9).contains(&d) {
+                        return Ok(Value::I32(value.parse::<i32>()?));
+                    } else if (10..=18).contains(&d) {
+                        return Ok(Value::I64(value.parse::<i64>()?));
                     }
-                    return Ok(Value::String(dec.to_string()).into_ext("Decimal"));
                 }
-                if s > 0 {
-                    let dec =
-                        BigDecimal::from_str(&value).map_err(|e| Error::from(e.to_string()))?;
-                    return Ok(Value::String(dec.to_string()).into_ext("Decimal"));
-                } else if 1 <= p && p <= 9 {
-                    let a = value.parse::<i32>()?;
-                    return Ok(Value::I32(a));
-                } else if 10 <= p && p <= 18 {
-                    let a = value.parse::<i64>()?;
-                    return Ok(Value::I64(a));
-                }
-                let dec = BigDecimal::from_str(&value).map_err(|e| Error::from(e.to_string()))?;
                 return Ok(Value::String(dec.to_string()).into_ext("Decimal"));
             }
-            DmDataType::SmallInt => {
-                let a = value.parse::<i32>()?;
-                return Ok(Value::I32(a));
-            }
-            DmDataType::Integer => {
-                let a = value.parse::<i32>()?;
-                return Ok(Value::I32(a));
-            }
-            // DmDataType::Int64 is integer
-            DmDataType::BigInt => {
-                let a = value.parse::<i64>()?;
-                return Ok(Value::I64(a));
-            }
-            DmDataType::Float { precision: p } => {
-                return if p >= 24 {
-                    let a = value.parse::<f64>()?;
-                    Ok(Value::F64(a))
-                } else {
-                    let a = value.parse::<f32>()?;
-                    Ok(Value::F32(a))
-                };
-            }
-            DmDataType::Double  => {
-                let a = value.parse::<f64>()?;
-                return Ok(Value::F64(a));
-            }
-            DmDataType::Binary { length: _ } => {
-                if let Some(a) = &row.data {
-                    return Ok(Value::Binary(a.clone()));
-                }
-                return Ok(Value::Null);
-            }
-            DmDataType::LongVarbinary { length: _ } => {
-                if let Some(a) = &row.data {
-                    return Ok(Value::Binary(a.clone()));
-                }
-                return Ok(Value::Null);
-            }
-            DmDataType::Char { length: _ } => {
-                return Ok(Value::String(value));
-            }
-            DmDataType::Varchar { length: _ } => {
-                return Ok(Value::String(value));
-            }
-            DmDataType::WChar { length: _ } => {
-                return Ok(Value::String(value));
-            }
-            DmDataType::WVarchar { length: _ } => {
-                return Ok(Value::String(value));
-            }
-            // DmDataType::CLOB => {
-            //     return Ok(Value::String(value))
-            // }
-            // DmDataType::LongVarchar { length: _ } => {
-            //     return Ok(Value::String(value));
-            // }
-            DmDataType::Date => {
-                let a = DateTime::from_str(&value)?;
-                // return Ok(Value::from(a));
-                return Ok(Value::Ext("Date", Box::new(Value::I64(a.unix_timestamp_millis()))));
-            }
-            DmDataType::Time { precision: _ } => {
-                // let date=FastDateTime::from_str(&value).unwrap().unix_timestamp_millis();
-                //  let timestamp=Timestamp::from_str(&value).unwrap();
-                //  // let tv=TV::new("Timestamp",Value::I64(date));
-                // return  Ok(Value::from(timestamp));
-                let date = DateTime::from_str(&value).unwrap().unix_timestamp_millis();
-                return Ok(Value::Ext("Time", Box::new(Value::I64(date))));
-                //
-                // let datetime=DateTime::from_str(&value).unwrap();
-                // return Ok(Value::from(datetime));
-            }
-            DmDataType::Timestamp { precision: _ } => {
-                // let date=FastDateTime::from_str(&value).unwrap().unix_timestamp_millis();
-                //  let timestamp=Timestamp::from_str(&value).unwrap();
-                //  // let tv=TV::new("Timestamp",Value::I64(date));
-                // return  Ok(Value::from(timestamp));
-                let date = DateTime::from_str(&value).unwrap().unix_timestamp_millis();
-                return Ok(Value::Ext("Timestamp", Box::new(Value::I64(date))));
-                //
-                // let datetime=DateTime::from_str(&value).unwrap();
-                // return Ok(Value::from(datetime));
-            }
-            DmDataType::Other { data_type, column_size: _, decimal_digits: _ } => {
-                return match data_type {
-                    SqlDataType::CHAR => { Ok(Value::String(value)) }
-                    SqlDataType::NUMERIC => { Ok(Value::String(value)) }
-                    SqlDataType::DECIMAL => { Ok(Value::String(value)) }
-                    SqlDataType::INTEGER => { Ok(Value::I32(value.parse::<i32>()?)) }
-                    SqlDataType::SMALLINT => { Ok(Value::I32(value.parse::<i32>()?)) }
-                    SqlDataType::FLOAT => { Ok(Value::F32(value.parse::<f32>()?)) }
-                    SqlDataType::REAL => { Ok(Value::F32(value.parse::<f32>()?)) }
-                    SqlDataType::DOUBLE => { Ok(Value::F64(value.parse::<f64>()?)) }
-                    SqlDataType::DATETIME => { Ok(Value::String(value)) }
-                    SqlDataType::VARCHAR => { Ok(Value::String(value)) }
-                    SqlDataType::DATE => { Ok(Value::String(value)) }
-                    SqlDataType::TIME => { Ok(Value::String(value)) }
-                    SqlDataType::TIMESTAMP => { Ok(Value::String(value)) }
-                    SqlDataType::EXT_TIME_OR_INTERVAL => { Ok(Value::String(value)) }
-                    SqlDataType::EXT_TIMESTAMP => { Ok(Value::String(value)) }
-                    SqlDataType::EXT_LONG_VARCHAR => { Ok(Value::String(value)) }
-                    SqlDataType::EXT_BINARY => { Ok(Value::String(value)) }
-                    SqlDataType::EXT_VAR_BINARY => { Ok(Value::String(value)) }
-                    SqlDataType::EXT_LONG_VAR_BINARY => { Ok(Value::String(value)) }
-                    SqlDataType::EXT_BIG_INT => { Ok(Value::I64(value.parse::<i64>()?)) }
-                    SqlDataType::EXT_TINY_INT => { Ok(Value::I32(value.parse::<i32>()?)) }
-                    SqlDataType::EXT_BIT => { Ok(Value::I32(value.parse::<i32>()?)) }
-                    SqlDataType::EXT_W_CHAR => { Ok(Value::String(value)) }
-                    SqlDataType::EXT_W_VARCHAR => { Ok(Value::String(value)) }
-                    SqlDataType::EXT_W_LONG_VARCHAR => { Ok(Value::String(value)) }
-                    SqlDataType::EXT_GUID => { Ok(Value::String(value)) }
-                    _ => { Ok(Value::String(value)) }
-                };
+            if s > 0 {
+                let dec = BigDecimal::from_str(&value).map_err(|e| Error::from(e.to_string()))?;
+                Ok(Value::String(dec.to_string()).into_ext("Decimal"))
+            } else if (1..=9).contains(&p) {
+                Ok(Value::I32(value.parse::<i32>()?))
+            } else if (10..=18).contains(&p) {
+                Ok(Value::I64(value.parse::<i64>()?))
+            } else {
+                let dec = BigDecimal::from_str(&value).map_err(|e| Error::from(e.to_string()))?;
+                Ok(Value::String(dec.to_string()).into_ext("Decimal"))
             }
-            _ => {
-                // return Err(Error::from(format!("Unsupported type: {:?}", row.column_type)));
-                return Ok(Value::String(value));
-                // if !value.is_empty() {
-                //     // 处理空数组的情况
-                //     match value.trim() {
-                //         "null" | "NULL" => {
-                //             return Ok(Value::Null);
-                //         }
-                //         "[]" => {
-                //             return Ok(Value::Array(vec![]));
-                //         }
-                //         "{}" => {
-                //             return Ok(Value::Map(ValueMap::new()));
-                //         }
-                //         _ => {
-                //             // return Ok("".to_string().into());
-                //         }
-                //     }
-                // 
-                //     if value.trim().starts_with("[") {
-                //         let value = value.trim();
-                // 
-                //         match serde_json::from_str::<Value>(&value) {
-                //             Ok(map) => {
-                //                 return Ok(map);
-                //             }
-                //             Err(_) => {
-                //                 return Ok("".to_string().into());
-                //             }
-                //         }
-                //     }
-                // 
-                //     if value.trim().starts_with("{") {
-                //         let value = value.trim();
-                //         match serde_json::from_str::<Value>(&value) {
-                //             Ok(map) => {
-                //                 return Ok(map);
-                //             }
-                //             Err(_) => {
-                //                 return Ok("".to_string().into());
-                //             }
-                //         }
-                //     }
-                //     return Ok(Value::String(value));
-                // 
-                // 
-                // } else {
-                //     return Ok("".to_string().into());
-                // }
+        }
+        DmDataType::SmallInt | DmDataType::Integer => Ok(Value::I32(value.parse::<i32>()?)),
+        // DmDataType::Int64 is integer
+        DmDataType::BigInt => Ok(Value::I64(value.parse::<i64>()?)),
+        DmDataType::Float { precision: p } => {
+            if p >= 24 {
+                Ok(Value::F64(value.parse::<f64>()?))
+            } else {
+                Ok(Value::F32(value.parse::<f32>()?))
             }
-        };
+        }
+        DmDataType::Double => Ok(Value::F64(value.parse::<f64>()?)),
+        DmDataType::Char { length: _ }
+        | DmDataType::Varchar { length: _ }
+        | DmDataType::WChar { length: _ }
+        | DmDataType::WVarchar { length: _ }
+        | DmDataType::LongVarchar { length: _ }
+        | DmDataType::WLongVarchar { length: _ } => Ok(Value::String(value)),
+        DmDataType::Date => {
+            let a = DateTime::from_str(&value)?;
+            Ok(Value::Ext("Date", Box::new(Value::I64(a.unix_timestamp_millis()))))
+        }
+        // 带小数秒的 TIME 按文本取回，如 `12:34:56.789`
+        DmDataType::Time { precision: _ } => Ok(Value::Ext("Time", Box::new(Value::String(value)))),
+        DmDataType::Timestamp { precision: _ } => {
+            let date = DateTime::from_str(&value)?.unix_timestamp_millis();
+            Ok(Value::Ext("Timestamp", Box::new(Value::I64(date))))
+        }
+        DmDataType::Other { data_type, column_size: _, decimal_digits: _ } => match data_type {
+            SqlDataType::INTEGER | SqlDataType::SMALLINT => Ok(Value::I32(value.parse::<i32>()?)),
+            SqlDataType::FLOAT | SqlDataType::REAL => Ok(Value::F32(value.parse::<f32>()?)),
+            SqlDataType::DOUBLE => Ok(Value::F64(value.parse::<f64>()?)),
+            SqlDataType::EXT_BIG_INT => Ok(Value::I64(value.parse::<i64>()?)),
+            SqlDataType::EXT_TINY_INT | SqlDataType::EXT_BIT => Ok(Value::I32(value.parse::<i32>()?)),
+            _ => Ok(Value::String(value)),
+        },
+        _ => Ok(Value::String(value)),
     }
 }
@@ -16,9 +16,12 @@ impl Driver for DamengDriver {
         "Dameng"
     }
 
-    fn connect(&self, _url: &str) -> BoxFuture<Result<Box<dyn Connection>, Error>> {
+    fn connect(&self, url: &str) -> BoxFuture<Result<Box<dyn Connection>, Error>> {
+        let url = url.to_owned();
         Box::pin(async move {
-            unimplemented!();
+            let opt = DamengConnectOptions::from_str(&url)?;
+            let conn = DamengConnection::establish(&opt).await?;
+            Ok(Box::new(conn) as Box<dyn Connection>)
         })
     }
 
@@ -26,8 +29,10 @@ impl Driver for DamengDriver {
         &'a self,
         opt: &'a dyn ConnectOptions,
     ) -> BoxFuture<Result<Box<dyn Connection>, Error>> {
-        let opt = opt.downcast_ref::<DamengConnectOptions>().unwrap();
         Box::pin(async move {
+            let opt = opt
+                .downcast_ref::<DamengConnectOptions>()
+                .ok_or_else(|| Error::from("connect_opt: expected DamengConnectOptions"))?;
             let conn = DamengConnection::establish(opt).await?;
             Ok(Box::new(conn) as Box<dyn Connection>)
         })
@@ -1,118 +1,213 @@
 use std::str::FromStr;
 
 use bigdecimal::BigDecimal;
+use odbc_api::parameter::{InputParameter, VarBinaryBox, VarCharBox, WithDataType};
+use odbc_api::sys::{Date, Time, Timestamp};
+use odbc_api::{Bit, DataType};
 use rbs::{Error, Value};
 
+/// 把 rbs 的 Value 转换为可以直接绑定到 ODBC 语句的参数
+///
+/// 数值、日期时间、二进制等都按原生类型绑定，避免在服务端做隐式转换。
 pub trait Encode {
-    fn encode(self, idx: usize) -> Result<String, Error>;
+    fn encode(self, idx: usize) -> Result<Box<dyn InputParameter>, Error>;
 }
 
-impl Encode for Value {
-    fn encode(self, _idx: usize) -> Result<String, Error> {
-        // let idx = idx + 1;//oracle is one-based
+/// 依次编码全部参数
+pub fn encode_params(params: Vec<Value>) -> Result<Vec<Box<dyn InputParameter>>, Error> {
+    params
+        .into_iter()
+        .enumerate()
+        .map(|(idx, v)| v.encode(idx))
+        .collect()
+}
+
+/// 编码全部参数，`NULL` 参数的 SQL 类型通过 `describe` 推断（参数序号从 1 开始）
+///
+/// `describe` 一般是对预编译语句调用 `SQLDescribeParam`；驱动不支持时返回 `None`，
+/// 此时不再继续尝试，剩余的 `NULL` 都按可空的 VARCHAR 绑定。
+pub fn encode_params_with<F>(
+    params: Vec<Value>,
+    mut describe: F,
+) -> Result<Vec<Box<dyn InputParameter>>, Error>
+where
+    F: FnMut(u16) -> Option<DataType>,
+{
+    let mut describe_supported = true;
+    let mut result = Vec::with_capacity(params.len());
+    for (idx, v) in params.into_iter().enumerate() {
+        if let Value::Null = v {
+            if describe_supported {
+                match describe(idx as u16 + 1) {
+                    Some(data_type) => {
+                        result.push(null_param(data_type));
+                        continue;
+                    }
+                    None => describe_supported = false,
+                }
+            }
+        }
+        result.push(v.encode(idx)?);
+    }
+    Ok(result)
+}
+
+/// 指定 SQL 类型的 NULL 参数
+pub fn null_param(data_type: DataType) -> Box<dyn InputParameter> {
+    match data_type {
+        DataType::Unknown => Box::new(VarCharBox::null()),
+        DataType::Binary { .. } | DataType::Varbinary { .. } | DataType::LongVarbinary { .. } => {
+            Box::new(WithDataType {
+                value: VarBinaryBox::null(),
+                data_type,
+            })
+        }
+        _ => Box::new(WithDataType {
+            value: VarCharBox::null(),
+            data_type,
+        }),
+    }
+}
+
+fn text(s: String) -> Box<dyn InputParameter> {
+    Box::new(VarCharBox::from_string(s))
+}
+
+/// 解析 `Date` 扩展类型的值
+pub fn date_value(s: &str) -> Result<Date, Error> {
+    let d = fastdate::Date::from_str(s).map_err(|e| Error::from(e.to_string()))?;
+    Ok(Date {
+        year: d.year as i16,
+        month: d.mon as u16,
+        day: d.day as u16,
+    })
+}
+
+/// 解析 `Time` 扩展类型的值，绑定时声明为 `TIME(0)`
+pub fn time_value(s: &str) -> Result<Time, Error> {
+    let t = fastdate::Time::from_str(s).map_err(|e| Error::from(e.to_string()))?;
+    Ok(Time {
+        hour: t.hour as u16,
+        minute: t.minute as u16,
+        second: t.sec as u16,
+    })
+}
+
+/// 达梦的 DATETIME/TIMESTAMP 不带时区，统一换算成本地时区的时间再绑定（与解码时按本地时区解析对应）
+///
+/// 绑定时声明为 `TIMESTAMP(6)`，达梦最多保留 6 位小数秒。
+pub fn timestamp_value(dt: fastdate::DateTime) -> Timestamp {
+    let dt = dt.set_offset(fastdate::offset_sec());
+    Timestamp {
+        year: dt.year() as i16,
+        month: dt.mon() as u16,
+        day: dt.day() as u16,
+        hour: dt.hour() as u16,
+        minute: dt.minute() as u16,
+        second: dt.sec() as u16,
+        fraction: dt.nano() / 1000 * 1000,
+    }
+}
+
+/// 解析 `DateTime` 扩展类型的值
+pub fn datetime_value(s: &str) -> Result<Timestamp, Error> {
+    let dt = fastdate::DateTime::from_str(s).map_err(|e| Error::from(e.to_string()))?;
+    Ok(timestamp_value(dt))
+}
+
+/// DECIMAL 的文本形式，去掉指数表示，负的标度补成 0
+pub fn decimal_text(s: &str) -> Result<String, Error> {
+    Ok(parse_decimal(s)?.to_plain_string())
+}
+
+fn parse_decimal(s: &str) -> Result<BigDecimal, Error> {
+    let d = BigDecimal::from_str(s.trim()).map_err(|e| Error::from(e.to_string()))?;
+    if d.as_bigint_and_exponent().1 < 0 {
+        Ok(d.with_scale(0))
+    } else {
+        Ok(d)
+    }
+}
+
+fn date_param(s: &str) -> Result<Box<dyn InputParameter>, Error> {
+    Ok(Box::new(date_value(s)?))
+}
 
+fn time_param(s: &str) -> Result<Box<dyn InputParameter>, Error> {
+    Ok(Box::new(WithDataType {
+        value: time_value(s)?,
+        data_type: DataType::Time { precision: 0 },
+    }))
+}
+
+fn timestamp_param(ts: Timestamp) -> Box<dyn InputParameter> {
+    Box::new(WithDataType {
+        value: ts,
+        data_type: DataType::Timestamp { precision: 6 },
+    })
+}
+
+/// 以文本形式绑定 DECIMAL，同时按实际值声明精度和标度
+fn decimal_param(s: &str) -> Result<Box<dyn InputParameter>, Error> {
+    let d = parse_decimal(s)?;
+    let (_, scale) = d.as_bigint_and_exponent();
+    let precision = (d.digits() as i64).max(scale).max(1);
+    Ok(Box::new(WithDataType {
+        value: VarCharBox::from_string(d.to_plain_string()),
+        data_type: DataType::Decimal {
+            precision: precision as usize,
+            scale: scale as i16,
+        },
+    }))
+}
+
+impl Encode for Value {
+    fn encode(self, _idx: usize) -> Result<Box<dyn InputParameter>, Error> {
         match self {
             Value::Ext(t, v) => match t {
-                "Date" => {
-                    let s = v.as_str().unwrap_or_default();
-                    // let _d = chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap();
-                    // statement.bind(idx, &d).map_err(|e| e.to_string())?
-                    return Ok(s.to_string());
-                }
-                "DateTime" => {
-                    let s = v.as_str().unwrap_or_default();
-                    // let _d = chrono::NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S%z").unwrap();
-                    // statement.bind(idx, &d).map_err(|e| e.to_string())?
-                    return Ok(s.to_string());
-                }
-                "Time" => {
-                    //TODO: need to fix this
-                    let s = v.into_string().unwrap();
-                    // statement.bind(idx, &s).map_err(|e| e.to_string())?
-                    return Ok(s.to_string());
-                }
-                "Decimal" => {
-                    let d = BigDecimal::from_str(&v.into_string().unwrap_or_default()).unwrap().to_string();
-                    // statement.bind(idx, &d).map_err(|e| e.to_string())?
-                    return Ok(d.to_string());
-                }
-                "Json" => {
-                    return Err(Error::from("unimpl"));
-                }
+                "Date" => date_param(v.as_str().unwrap_or_default()),
+                "DateTime" => Ok(timestamp_param(datetime_value(v.as_str().unwrap_or_default())?)),
+                "Time" => time_param(v.as_str().unwrap_or_default()),
                 "Timestamp" => {
-                    let t = v.as_u64().unwrap_or_default() as i64;
-                    // statement.bind(idx, &t).map_err(|e| e.to_string())?
-                    return Ok(t.to_string());
+                    let t = v.as_i64().unwrap_or_default();
+                    Ok(timestamp_param(timestamp_value(fastdate::DateTime::from_timestamp_millis(t))))
                 }
-                "Uuid" => {
-                    let d = v.into_string().unwrap();
-                    // statement.bind(idx, &d).map_err(|e| e.to_string())?
-                    return Ok(d);
-                }
-                _ => {
-                    return Err(Error::from("unimpl"));
-                }
-            }
-            Value::String(str) => {
-                // statement.bind(idx, &str).map_err(|e| e.to_string())?
-                return Ok(str);
-            }
-            Value::U32(u) => {
-                // statement.bind(idx, &u).map_err(|e| e.to_string())?
-                return Ok(u.to_string());
-            }
-            Value::U64(u) => {
-                // statement.bind(idx, &u).map_err(|e| e.to_string())?
-                return Ok(u.to_string());
-            }
-            Value::I32(int) => {
-                // statement.bind(idx, &int).map_err(|e| e.to_string())?
-                return Ok(int.to_string());
-            }
-            Value::I64(long) => {
-                // statement.bind(idx, &long).map_err(|e| e.to_string())
-                return Ok(long.to_string());
-            }
-            Value::F32(float) => {
-                // statement.bind(idx, &float).map_err(|e| e.to_string())?
-                return Ok(float.to_string());
-            }
-            Value::F64(double) => {
-                // statement.bind(idx, &double).map_err(|e| e.to_string())?
-                return Ok(double.to_string());
-            }
-            Value::Binary(_bin) => {
-                // statement.bind(idx, &bin).map_err(|e| e.to_string())?
-                return Err(Error::from("unimpl"));
-            }
-            Value::Null => {
-                // statement.bind(idx, &Option::<String>::None).unwrap();
-                return Ok("NULL".to_string());
-            }
+                "Decimal" => decimal_param(&v.into_string().unwrap_or_default()),
+                "Json" | "Uuid" => match *v {
+                    Value::String(s) => Ok(text(s)),
+                    Value::Binary(b) => Ok(Box::new(VarBinaryBox::from_vec(b))),
+                    other => Ok(text(other.to_string())),
+                },
+                _ => Err(Error::from(format!("unsupported ext type: {}", t))),
+            },
+            Value::String(s) => Ok(text(s)),
+            Value::Bool(b) => Ok(Box::new(Bit::from_bool(b))),
+            Value::U32(u) => Ok(Box::new(u as i64)),
+            Value::U64(u) => match i64::try_from(u) {
+                Ok(v) => Ok(Box::new(v)),
+                Err(_) => decimal_param(&u.to_string()),
+            },
+            Value::I32(v) => Ok(Box::new(v)),
+            Value::I64(v) => Ok(Box::new(v)),
+            Value::F32(v) => Ok(Box::new(v)),
+            Value::F64(v) => Ok(Box::new(v)),
+            Value::Binary(b) => Ok(Box::new(VarBinaryBox::from_vec(b))),
+            // 无法得知参数类型时按可空的 VARCHAR 绑定
+            Value::Null => Ok(Box::new(VarCharBox::null())),
             Value::Array(arr) => {
-
                 if arr.is_empty() {
-                    return Ok("[]".to_string());
+                    return Ok(text("[]".to_string()));
                 }
-
-                let s = serde_json::to_string(&arr).unwrap();
-                return Ok(s);
+                let s = serde_json::to_string(&arr).map_err(|e| Error::from(e.to_string()))?;
+                Ok(text(s))
             }
-            Value::Map(arr) => {
-
-                if arr.is_empty() {
-                    return Ok("{}".to_string());
+            Value::Map(m) => {
+                if m.is_empty() {
+                    return Ok(text("{}".to_string()));
                 }
-
-                let s = serde_json::to_string(&arr).unwrap();
-                return Ok(s);
-            }
-            //TODO: more types!
-            _ => {
-                // statement.bind(idx, &self.to_string()).map_err(|e| e.to_string())?
-                return Ok(self.to_string());
+                let s = serde_json::to_string(&m).map_err(|e| Error::from(e.to_string()))?;
+                Ok(text(s))
             }
         }
-        // Ok(())
     }
 }
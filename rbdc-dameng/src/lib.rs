@@ -9,11 +9,20 @@ pub use driver::DamengDriver as Driver;
 use crate::common::data_type::DmDataType;
 use crate::decode::Decode;
 
+pub mod batch;
+pub mod buffer;
+pub mod cancel;
 pub mod common;
 pub mod decode;
 pub mod driver;
 pub mod encode;
+pub mod lob;
 pub mod options;
+pub mod reconnect;
+pub mod results;
+pub mod statement_cache;
+pub mod transaction;
+pub mod worker;
 pub mod connection;
 
 #[derive(Debug, Clone)]
@@ -40,13 +49,59 @@ impl MetaData for DamengMetaData {
     }
 }
 
+/// 取数缓冲区中的一个非 NULL 值
+///
+/// 定长类型保留驱动填充的原生值，其余类型是驱动返回的文本或二进制。
+#[derive(Debug, Clone, PartialEq)]
+pub enum CellValue {
+    Bytes(Vec<u8>),
+    I16(i16),
+    I32(i32),
+    I64(i64),
+    F32(f32),
+    F64(f64),
+    Date(odbc_api::sys::Date),
+    Time(odbc_api::sys::Time),
+    Timestamp(odbc_api::sys::Timestamp),
+}
+
 #[derive(Debug)]
 pub struct DamengData {
-    pub data: Option<Vec<u8>>,
+    pub data: Option<CellValue>,
     pub column_type: DmDataType,
     pub is_sql_null: bool,
 }
 
+impl DamengData {
+    pub fn new(column_type: DmDataType, data: Vec<u8>) -> Self {
+        Self::with_value(column_type, CellValue::Bytes(data))
+    }
+
+    pub fn with_value(column_type: DmDataType, value: CellValue) -> Self {
+        Self {
+            data: Some(value),
+            column_type,
+            is_sql_null: false,
+        }
+    }
+
+    /// 由 [`buffer::cell_at`] 的结果构造，`None` 即 SQL NULL
+    pub fn from_cell(column_type: DmDataType, cell: Option<CellValue>) -> Self {
+        match cell {
+            Some(value) => Self::with_value(column_type, value),
+            None => Self::null(column_type),
+        }
+    }
+
+    pub fn null(column_type: DmDataType) -> Self {
+        Self {
+            data: None,
+            column_type,
+            is_sql_null: true,
+        }
+    }
+}
+
 
 #[derive(Debug)]
 pub struct DamengRow {
@@ -54,6 +109,31 @@ pub struct DamengRow {
     pub datas: Vec<DamengData>,
 }
 
+/// 语句返回的一个结果：查询部分带列信息和行，其它部分（INSERT / UPDATE 等）只有影响的行数
+#[derive(Debug)]
+pub struct ResultSet {
+    pub columns: Arc<Vec<DamengColumn>>,
+    pub rows: Vec<DamengRow>,
+    /// 非查询部分影响的行数，查询结果为 `None`
+    pub rows_affected: Option<u64>,
+}
+
+impl ResultSet {
+    /// 非查询部分的结果
+    pub fn affected(rows_affected: u64) -> Self {
+        Self {
+            columns: Arc::new(vec![]),
+            rows: vec![],
+            rows_affected: Some(rows_affected),
+        }
+    }
+
+    /// 是否为查询返回的结果集（可能没有行）
+    pub fn is_query(&self) -> bool {
+        self.rows_affected.is_none()
+    }
+}
+
 
 impl Row for DamengRow {
     fn meta_data(&self) -> Box<dyn MetaData> {
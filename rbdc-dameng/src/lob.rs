@@ -0,0 +1,286 @@
+//! CLOB / TEXT / BLOB / IMAGE 等大字段的流式读写
+//!
+//! 读取时用 [`LobReader`] 按块取回单个大字段，写入时用 [`LobParam`] 把 `BufRead` 作为
+//! data-at-execution 参数，执行语句时通过 `SQLPutData` 分块发送，两者都不需要把整个值放进内存。
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::num::NonZeroUsize;
+use std::path::Path;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_core::Stream;
+use futures_io::AsyncRead;
+use odbc_api::buffers::Indicator;
+use odbc_api::handles::{CDataMut, HasDataType, Statement};
+use odbc_api::parameter::{Blob, BlobParam, CElement, InputParameter, VarBinarySliceMut, VarCharSliceMut};
+use odbc_api::sys::CDataType;
+use odbc_api::{CursorRow, DataType, ParameterCollection};
+use rbdc::Error;
+use rbs::Value;
+use tokio::sync::mpsc;
+
+use crate::cancel::CancelOnDrop;
+use crate::connection::OpenStream;
+
+/// 每次 `SQLGetData` 读取的字节数
+pub const LOB_CHUNK_SIZE: usize = 64 * 1024;
+
+/// 可以对当前行调用 `SQLGetData` 的对象：游标的当前行，或块游标中定位到的一行（[`crate::buffer::BlockRow`]）
+pub trait GetData {
+    fn get_data(&mut self, col: u16, target: &mut (impl CElement + CDataMut)) -> Result<(), odbc_api::Error>;
+}
+
+impl GetData for CursorRow<'_> {
+    fn get_data(&mut self, col: u16, target: &mut (impl CElement + CDataMut)) -> Result<(), odbc_api::Error> {
+        CursorRow::get_data(self, col, target)
+    }
+}
+
+/// 按块读取当前行某一列的大字段，值为 NULL 时不产生任何块并返回 `false`
+///
+/// `on_chunk` 返回 `false` 时停止读取。
+pub fn read_chunks<F>(
+    row: &mut impl GetData,
+    col: u16,
+    binary: bool,
+    chunk_size: usize,
+    mut on_chunk: F,
+) -> Result<bool, odbc_api::Error>
+where
+    F: FnMut(Vec<u8>) -> bool,
+{
+    // 文本末尾要给驱动留一个字节写 '\0'
+    let mut buf = vec![0u8; chunk_size.max(1) + usize::from(!binary)];
+    loop {
+        let (chunk, complete) = if binary {
+            let mut cell = VarBinarySliceMut::from_buffer(&mut buf, Indicator::NoTotal);
+            row.get_data(col, &mut cell)?;
+            (cell.as_bytes().map(<[u8]>::to_vec), cell.is_complete())
+        } else {
+            let mut cell = VarCharSliceMut::from_buffer(&mut buf, Indicator::NoTotal);
+            row.get_data(col, &mut cell)?;
+            (cell.as_bytes().map(<[u8]>::to_vec), cell.is_complete())
+        };
+        match chunk {
+            None => return Ok(false),
+            Some(chunk) => {
+                if !chunk.is_empty() && !on_chunk(chunk) {
+                    return Ok(true);
+                }
+            }
+        }
+        if complete {
+            return Ok(true);
+        }
+    }
+}
+
+/// 按块读取大字段的结果，由 [`crate::connection::DamengConnection::read_lob`] 返回
+///
+/// 既是逐块产出 `Vec<u8>` 的 [`Stream`]，也实现了 [`AsyncRead`]，可以直接拷贝到文件等目标。
+/// 后台线程最多领先一个块，丢弃读取器会停止读取并关闭游标。
+pub struct LobReader {
+    rx: mpsc::Receiver<Result<Vec<u8>, Error>>,
+    chunk: Vec<u8>,
+    pos: usize,
+    /// 读取器释放时取消仍在执行的查询
+    _cancel: Option<CancelOnDrop>,
+    /// 读完或释放前占着连接，见 [`crate::connection::DamengConnection::read_lob`]
+    open: Option<OpenStream>,
+}
+
+impl LobReader {
+    /// 从按块发送的通道构造读取器，通道关闭即读取结束
+    pub fn new(rx: mpsc::Receiver<Result<Vec<u8>, Error>>) -> Self {
+        Self {
+            rx,
+            chunk: vec![],
+            pos: 0,
+            _cancel: None,
+            open: None,
+        }
+    }
+
+    pub(crate) fn cancel_on_drop(mut self, cancel: CancelOnDrop) -> Self {
+        self._cancel = Some(cancel);
+        self
+    }
+
+    pub(crate) fn hold(mut self, open: OpenStream) -> Self {
+        self.open = Some(open);
+        self
+    }
+
+    fn poll_chunk(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<Vec<u8>, Error>>> {
+        let chunk = self.rx.poll_recv(cx);
+        if let Poll::Ready(None) = chunk {
+            self.open = None;
+        }
+        chunk
+    }
+}
+
+impl Stream for LobReader {
+    type Item = Result<Vec<u8>, Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        // 先交出 AsyncRead 读剩下的部分
+        if this.pos < this.chunk.len() {
+            let rest = this.chunk.split_off(this.pos);
+            this.pos = 0;
+            this.chunk.clear();
+            return Poll::Ready(Some(Ok(rest)));
+        }
+        this.poll_chunk(cx)
+    }
+}
+
+impl AsyncRead for LobReader {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        while this.pos >= this.chunk.len() {
+            match this.poll_chunk(cx) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(None) => return Poll::Ready(Ok(0)),
+                Poll::Ready(Some(Err(e))) => return Poll::Ready(Err(io::Error::other(e.to_string()))),
+                Poll::Ready(Some(Ok(chunk))) => {
+                    this.chunk = chunk;
+                    this.pos = 0;
+                }
+            }
+        }
+        let n = buf.len().min(this.chunk.len() - this.pos);
+        buf[..n].copy_from_slice(&this.chunk[this.pos..this.pos + n]);
+        this.pos += n;
+        Poll::Ready(Ok(n))
+    }
+}
+
+/// 以流的方式写入的大字段参数
+///
+/// ```no_run
+/// use rbdc_dameng::lob::{LobParam, Param};
+///
+/// let params: Vec<Param> = vec![
+///     rbs::Value::I32(1).into(),
+///     LobParam::from_path("photo.png".as_ref()).unwrap().into(),
+/// ];
+/// ```
+pub struct LobParam {
+    reader: Box<dyn BufRead + Send>,
+    binary: bool,
+    size: Option<usize>,
+    consume: usize,
+}
+
+impl LobParam {
+    /// 二进制大字段（BLOB / IMAGE）
+    pub fn binary(reader: impl BufRead + Send + 'static) -> Self {
+        Self {
+            reader: Box::new(reader),
+            binary: true,
+            size: None,
+            consume: 0,
+        }
+    }
+
+    /// 文本大字段（CLOB / TEXT），内容按客户端字符集编码
+    pub fn text(reader: impl BufRead + Send + 'static) -> Self {
+        Self {
+            binary: false,
+            ..Self::binary(reader)
+        }
+    }
+
+    /// 以文件内容作为二进制大字段，文件大小会提前告知驱动
+    pub fn from_path(path: &Path) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let size = usize::try_from(file.metadata()?.len()).map_err(io::Error::other)?;
+        Ok(Self {
+            size: Some(size),
+            ..Self::binary(BufReader::new(file))
+        })
+    }
+}
+
+impl HasDataType for LobParam {
+    fn data_type(&self) -> DataType {
+        let length = self.size.and_then(NonZeroUsize::new);
+        if self.binary {
+            DataType::LongVarbinary { length }
+        } else {
+            DataType::LongVarchar { length }
+        }
+    }
+}
+
+unsafe impl Blob for LobParam {
+    fn c_data_type(&self) -> CDataType {
+        if self.binary {
+            CDataType::Binary
+        } else {
+            CDataType::Char
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.size
+    }
+
+    fn next_batch(&mut self) -> io::Result<Option<&[u8]>> {
+        if self.consume != 0 {
+            self.reader.consume(self.consume);
+        }
+        let batch = self.reader.fill_buf()?;
+        self.consume = batch.len();
+        if batch.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(batch))
+        }
+    }
+}
+
+/// 普通参数或者流式大字段参数，用于 [`crate::connection::DamengConnection::exec_with_lobs`]
+pub enum Param {
+    Value(Value),
+    Lob(LobParam),
+}
+
+impl From<Value> for Param {
+    fn from(value: Value) -> Self {
+        Param::Value(value)
+    }
+}
+
+impl From<LobParam> for Param {
+    fn from(lob: LobParam) -> Self {
+        Param::Lob(lob)
+    }
+}
+
+/// 同时包含普通参数和 data-at-execution 参数的参数集合，序号从 1 开始
+pub(crate) struct MixedParams<'a> {
+    pub values: Vec<(u16, Box<dyn InputParameter>)>,
+    pub lobs: Vec<(u16, BlobParam<'a>)>,
+}
+
+unsafe impl ParameterCollection for MixedParams<'_> {
+    fn parameter_set_size(&self) -> usize {
+        1
+    }
+
+    unsafe fn bind_parameters_to(&mut self, stmt: &mut impl Statement) -> Result<(), odbc_api::Error> {
+        for (n, param) in &self.values {
+            param.assert_completness();
+            unsafe { stmt.bind_input_parameter(*n, param.as_ref()) }.into_result(stmt)?;
+        }
+        for (n, lob) in &mut self.lobs {
+            unsafe { stmt.bind_delayed_input_parameter(*n, lob) }.into_result(stmt)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,87 @@
+//! 连接断开后的自动重连
+//!
+//! 达梦重启或网络闪断后，原来的 ODBC 连接上所有调用都会返回 08xxx 等连接错误。
+//! 不在事务中时，[`with_reconnect`] 按 [`ReconnectPolicy`] 退避重试：用保存的连接参数重新建立连接，
+//! 替换掉断开的连接后再执行一次操作。事务中的连接断开时服务端已经回滚了事务，只能把错误交给调用方。
+//! 已经发给服务端的写操作也不重试：连接断开时无法知道它是否已经生效，重新执行可能重复写入。
+//!
+//! 重连逻辑只依赖“建立连接”和“在连接上执行操作”两个闭包，与具体的连接类型无关。
+
+use std::thread;
+
+use rbdc::Error;
+
+use crate::common::error::DamengErrorExt;
+use crate::options::ReconnectPolicy;
+
+/// 在 `conn` 上执行 `op`，连接断开时按策略重连后重试
+///
+/// - `may_retry` 在每次失败后以当前连接调用，返回 `false` 时不再重连（如已在事务中，或者已经向调用方交出了部分结果）；
+/// - `connect` 建立新的连接，成功后替换 `conn`；
+/// - 重连本身也因连接错误失败时继续退避重试，其它错误直接返回。
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use rbdc::Error;
+/// use rbdc_dameng::options::ReconnectPolicy;
+/// use rbdc_dameng::reconnect::with_reconnect;
+///
+/// let policy = ReconnectPolicy::new(3, Duration::ZERO, Duration::ZERO);
+/// // 断开的连接用 false 表示
+/// let mut conn = false;
+/// let result = with_reconnect(
+///     &mut conn,
+///     &policy,
+///     |_| true,
+///     || Ok(true),
+///     |alive| if *alive { Ok(1) } else { Err(Error::from("[SQLSTATE 08S01, DM -70019] 网络通信异常")) },
+/// );
+/// assert_eq!(result.unwrap(), 1);
+/// assert!(conn);
+/// ```
+pub fn with_reconnect<C, T, R, N, O>(
+    conn: &mut C,
+    policy: &ReconnectPolicy,
+    may_retry: R,
+    mut connect: N,
+    mut op: O,
+) -> Result<T, Error>
+where
+    R: Fn(&C) -> bool,
+    N: FnMut() -> Result<C, Error>,
+    O: FnMut(&mut C) -> Result<T, Error>,
+{
+    let mut error = match op(conn) {
+        Ok(v) => return Ok(v),
+        Err(e) => e,
+    };
+    for attempt in 0..policy.max_retries {
+        if !error.is_connection_broken() || !may_retry(conn) {
+            break;
+        }
+        let backoff = policy.backoff(attempt);
+        log::warn!(
+            "connection broken ({}), reconnect in {:?} (attempt {}/{})",
+            error,
+            backoff,
+            attempt + 1,
+            policy.max_retries
+        );
+        if !backoff.is_zero() {
+            thread::sleep(backoff);
+        }
+        match connect() {
+            Ok(new_conn) => {
+                *conn = new_conn;
+                log::info!("reconnected");
+                match op(conn) {
+                    Ok(v) => return Ok(v),
+                    Err(e) => error = e,
+                }
+            }
+            Err(e) => error = e,
+        }
+    }
+    Err(error)
+}
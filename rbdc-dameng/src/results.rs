@@ -0,0 +1,70 @@
+//! 依次读取语句返回的全部结果
+//!
+//! 存储过程或多条语句组成的批处理会返回多个结果：查询部分是结果集，INSERT / UPDATE 等部分只有影响的行数。
+//! 读完当前结果后调用 `SQLMoreResults` 移到下一个，直到没有更多结果。
+//!
+//! [`collect_results`] 只依赖 [`ResultSource`] 上的几个操作，与具体的语句类型无关。
+
+use rbdc::Error;
+
+use crate::ResultSet;
+
+/// 语句执行后依次产生的结果，测试中可以换成模拟的语句
+pub trait ResultSource {
+    /// 当前结果的列数，0 表示不是结果集
+    fn num_result_cols(&mut self) -> Result<usize, Error>;
+    /// 当前非查询结果影响的行数，驱动没有提供时为 0
+    fn row_count(&mut self) -> u64;
+    /// 读取当前结果集的全部行；第二个值为 `false` 时游标已经关闭，后面的结果随之丢弃
+    fn read_result_set(&mut self) -> Result<(ResultSet, bool), Error>;
+    /// 移到下一个结果（`SQLMoreResults`），没有更多结果时返回 `false`
+    fn more_results(&mut self) -> Result<bool, Error>;
+}
+
+/// 按顺序读取 `source` 上从当前结果开始的全部结果，没有行的结果集也会保留
+///
+/// ```
+/// use rbdc::Error;
+/// use rbdc_dameng::results::{collect_results, ResultSource};
+/// use rbdc_dameng::ResultSet;
+///
+/// /// 只返回一个影响了 2 行的结果
+/// struct Update;
+///
+/// impl ResultSource for Update {
+///     fn num_result_cols(&mut self) -> Result<usize, Error> {
+///         Ok(0)
+///     }
+///     fn row_count(&mut self) -> u64 {
+///         2
+///     }
+///     fn read_result_set(&mut self) -> Result<(ResultSet, bool), Error> {
+///         unreachable!()
+///     }
+///     fn more_results(&mut self) -> Result<bool, Error> {
+///         Ok(false)
+///     }
+/// }
+///
+/// let results = collect_results(&mut Update).unwrap();
+/// assert_eq!(results.len(), 1);
+/// assert_eq!(results[0].rows_affected, Some(2));
+/// ```
+pub fn collect_results(source: &mut impl ResultSource) -> Result<Vec<ResultSet>, Error> {
+    let mut results = vec![];
+    loop {
+        if source.num_result_cols()? > 0 {
+            let (result, open) = source.read_result_set()?;
+            results.push(result);
+            if !open {
+                break;
+            }
+        } else {
+            results.push(ResultSet::affected(source.row_count()));
+        }
+        if !source.more_results()? {
+            break;
+        }
+    }
+    Ok(results)
+}
@@ -0,0 +1,131 @@
+//! 预编译语句缓存
+//!
+//! 每个连接按 SQL 文本缓存最近使用的预编译语句，再次执行相同的 SQL 时省去 `SQLPrepare` 的往返。
+//! 超出容量时淘汰最久未用的语句。执行 DDL 或服务端报告对象不存在（表结构已变化）时清空缓存，
+//! 见 [`crate::common::sql::changes_schema`] 和 `DamengErrorExt::is_schema_changed`。
+//!
+//! 缓存本身与语句类型无关，执行期间语句从缓存中取出，执行成功后再放回。
+
+use std::num::NonZeroUsize;
+use std::ops::{Deref, DerefMut};
+use std::rc::Rc;
+
+use lru::LruCache;
+
+/// 语句缓存的命中统计
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CacheStats {
+    /// 从缓存中取到语句的次数
+    pub hits: u64,
+    /// 缓存中没有、需要重新准备的次数
+    pub misses: u64,
+    /// 当前缓存的语句数
+    pub len: usize,
+    /// 缓存容量，0 表示不缓存
+    pub capacity: usize,
+}
+
+/// 按 SQL 文本缓存语句，容量为 0 时不缓存，只统计未命中次数
+///
+/// ```
+/// use rbdc_dameng::statement_cache::StatementCache;
+///
+/// let mut cache = StatementCache::new(1);
+/// assert_eq!(cache.take("select 1"), None);
+/// cache.put("select 1", 1);
+/// assert_eq!(cache.take("select 1"), Some(1));
+/// assert_eq!((cache.stats().hits, cache.stats().misses), (1, 1));
+/// ```
+pub struct StatementCache<S> {
+    statements: Option<LruCache<String, S>>,
+    hits: u64,
+    misses: u64,
+}
+
+impl<S> StatementCache<S> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            statements: NonZeroUsize::new(capacity).map(LruCache::new),
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// 取出 SQL 对应的语句，语句用完后需要 [`put`](Self::put) 放回
+    pub fn take(&mut self, sql: &str) -> Option<S> {
+        let stmt = self.statements.as_mut().and_then(|statements| statements.pop(sql));
+        if stmt.is_some() {
+            self.hits += 1;
+        } else {
+            self.misses += 1;
+        }
+        stmt
+    }
+
+    /// 放回语句，缓存已满时淘汰最久未用的语句
+    pub fn put(&mut self, sql: &str, stmt: S) {
+        if let Some(statements) = &mut self.statements {
+            if let Some((evicted, _)) = statements.push(sql.to_string(), stmt) {
+                if evicted != sql {
+                    log::debug!("statement cache full, evict: {}", evicted);
+                }
+            }
+        }
+    }
+
+    /// 释放所有缓存的语句，统计数据保留
+    pub fn clear(&mut self) {
+        if let Some(statements) = &mut self.statements {
+            statements.clear();
+        }
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits,
+            misses: self.misses,
+            len: self.statements.as_ref().map_or(0, LruCache::len),
+            capacity: self.statements.as_ref().map_or(0, |statements| statements.cap().get()),
+        }
+    }
+}
+
+/// 借用了 `owner` 的值，和 owner 的一份 `Rc` 放在一起，保证 owner 比它后释放
+///
+/// 缓存的预编译语句借用连接：语句可能比会话中的连接引用活得久（如会话释放时缓存还没清空），
+/// 每条语句各自持有一份连接，连接在最后一条语句释放后才断开。
+pub struct WithOwner<T, O> {
+    // 不变量：字段按声明顺序释放，value 必须在 _owner 之前，调整字段顺序会让 value 借用已经释放的 owner
+    value: T,
+    _owner: Rc<O>,
+}
+
+impl<T, O: 'static> WithOwner<T, O> {
+    /// 用 `owner` 的引用构造值，构造失败时返回 `make` 的错误
+    ///
+    /// # Safety
+    ///
+    /// `make` 得到的 `'static` 引用只能保存在返回的值里，不能放到别处，否则引用可能比 owner 活得久。
+    pub unsafe fn new<E>(owner: &Rc<O>, make: impl FnOnce(&'static O) -> Result<T, E>) -> Result<Self, E> {
+        let owner = owner.clone();
+        // SAFETY: owner 分配在 Rc 的堆内存上，地址不变；返回的结构体持有这个 Rc，且 value 字段先于 owner
+        // 释放（见字段顺序上的不变量），value 存在期间引用一直有效；调用方保证引用不会逃出 value
+        let borrowed: &'static O = unsafe { &*Rc::as_ptr(&owner) };
+        let value = make(borrowed)?;
+        Ok(Self { value, _owner: owner })
+    }
+}
+
+impl<T, O> Deref for WithOwner<T, O> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T, O> DerefMut for WithOwner<T, O> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
@@ -0,0 +1,275 @@
+//! 事务状态
+//!
+//! ODBC 没有显式的 BEGIN：关闭自动提交即开始事务，`SQLEndTran` 提交或回滚后再打开自动提交。
+//! rbatis 通过 `exec("begin" / "commit" / "rollback")` 驱动事务，这里把这几条语句
+//! 翻译成对应的 ODBC 调用，并记录当前连接是否处于事务中。
+//!
+//! 事务可以嵌套：事务中再次 `begin` 会创建保存点 `sp_n`（n 为嵌套层数），内层的提交和回滚
+//! 分别对应 `RELEASE SAVEPOINT sp_n` 和 `ROLLBACK TO SAVEPOINT sp_n`，只撤销内层的修改，
+//! 外层事务不受影响。
+
+use odbc_api::Connection as OdbcApiConnection;
+use rbdc::Error;
+
+use crate::common::error::{odbc_error, DamengErrorExt};
+use crate::options::TxOptions;
+
+/// 事务用到的连接操作，测试中可以换成模拟的连接
+pub trait TxConnection {
+    fn set_autocommit(&self, enabled: bool) -> Result<(), Error>;
+    fn commit(&self) -> Result<(), Error>;
+    fn rollback(&self) -> Result<(), Error>;
+    /// 执行不返回结果集的语句，如 `SAVEPOINT sp_1`
+    fn execute(&self, sql: &str) -> Result<(), Error>;
+}
+
+impl TxConnection for OdbcApiConnection<'_> {
+    fn set_autocommit(&self, enabled: bool) -> Result<(), Error> {
+        OdbcApiConnection::set_autocommit(self, enabled).map_err(odbc_error)
+    }
+
+    fn commit(&self) -> Result<(), Error> {
+        OdbcApiConnection::commit(self).map_err(odbc_error)
+    }
+
+    fn rollback(&self) -> Result<(), Error> {
+        OdbcApiConnection::rollback(self).map_err(odbc_error)
+    }
+
+    fn execute(&self, sql: &str) -> Result<(), Error> {
+        OdbcApiConnection::execute(self, sql, (), None).map(|_| ()).map_err(odbc_error)
+    }
+}
+
+/// 事务控制语句
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TxCommand {
+    Begin,
+    Commit,
+    Rollback,
+}
+
+impl TxCommand {
+    /// 识别事务控制语句，不区分大小写，允许 `begin transaction`、`commit work` 等写法和末尾的分号
+    ///
+    /// ```
+    /// use rbdc_dameng::transaction::TxCommand;
+    ///
+    /// assert_eq!(TxCommand::parse("begin"), Some(TxCommand::Begin));
+    /// assert_eq!(TxCommand::parse(" COMMIT WORK; "), Some(TxCommand::Commit));
+    /// assert_eq!(TxCommand::parse("rollback to savepoint sp_1"), None);
+    /// ```
+    pub fn parse(sql: &str) -> Option<Self> {
+        let sql = sql.trim().trim_end_matches(';').to_lowercase();
+        let mut words = sql.split_whitespace();
+        let cmd = match words.next()? {
+            "begin" => TxCommand::Begin,
+            "start" if words.next()? == "transaction" => TxCommand::Begin,
+            "commit" => TxCommand::Commit,
+            "rollback" => TxCommand::Rollback,
+            _ => return None,
+        };
+        match (cmd, words.next()) {
+            (_, None) => Some(cmd),
+            (TxCommand::Begin, Some("transaction" | "work")) if words.next().is_none() => Some(cmd),
+            (TxCommand::Commit | TxCommand::Rollback, Some("work")) if words.next().is_none() => Some(cmd),
+            _ => None,
+        }
+    }
+}
+
+/// 事务所处的状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TxState {
+    /// 自动提交模式
+    #[default]
+    Idle,
+    /// 已关闭自动提交，等待提交或回滚
+    Active,
+}
+
+/// 已处于 `depth` 层事务中时，再次 `begin` 创建的保存点名，第一层嵌套为 `sp_1`
+pub fn savepoint_name(depth: usize) -> String {
+    format!("sp_{}", depth)
+}
+
+/// 一个物理连接上的事务状态
+///
+/// 与连接一起由连接的工作线程持有，所有方法的 `conn` 都必须是这个连接。
+/// 工作线程退出时如果事务仍未结束，会自动回滚，见 [`crate::connection`]。
+#[derive(Debug)]
+pub struct Transaction {
+    /// `begin` 语句使用的默认参数
+    defaults: TxOptions,
+    /// 嵌套层数，0 表示不在事务中
+    depth: usize,
+    /// 事务结束后恢复自动提交失败，执行下一条语句前重试，见 [`Transaction::ensure_autocommit`]
+    autocommit_pending: bool,
+}
+
+impl Transaction {
+    pub fn new(defaults: TxOptions) -> Self {
+        Self { defaults, depth: 0, autocommit_pending: false }
+    }
+
+    pub fn state(&self) -> TxState {
+        if self.depth() == 0 {
+            TxState::Idle
+        } else {
+            TxState::Active
+        }
+    }
+
+    /// 当前的嵌套层数，0 表示不在事务中
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// 执行事务控制语句
+    ///
+    /// 没有事务时的提交和回滚什么也不做；`begin` 使用连接上的默认事务参数。
+    pub fn execute(&mut self, conn: &impl TxConnection, cmd: TxCommand) -> Result<(), Error> {
+        let defaults = self.defaults;
+        match cmd {
+            TxCommand::Begin => self.begin(conn, &defaults),
+            TxCommand::Commit => self.end(conn, true),
+            TxCommand::Rollback => self.end(conn, false),
+        }
+    }
+
+    /// 按指定的参数开始事务，已在事务中时创建保存点，参数被忽略
+    pub fn begin(&mut self, conn: &impl TxConnection, options: &TxOptions) -> Result<(), Error> {
+        let depth = self.depth;
+        if depth > 0 {
+            if options != &self.defaults {
+                log::debug!("{:?} ignored, nested transaction keeps the outer settings", options);
+            }
+            let sql = format!("SAVEPOINT {}", savepoint_name(depth));
+            if let Err(e) = Self::execute_sql(conn, &sql) {
+                self.lost_if_broken(conn, &e);
+                return Err(e);
+            }
+            self.depth += 1;
+            return Ok(());
+        }
+
+        conn.set_autocommit(false)
+            .map_err(|e| Error::from(format!("begin transaction failed: {}", e)))?;
+        self.autocommit_pending = false;
+        // SET TRANSACTION 必须是事务中的第一条语句
+        if let Some(sql) = options.statement() {
+            if let Err(e) = Self::execute_sql(conn, &sql) {
+                let _ = conn.rollback();
+                self.restore_autocommit(conn);
+                return Err(e);
+            }
+        }
+        self.depth = 1;
+        Ok(())
+    }
+
+    /// 提交（`commit` 为 `true`）或回滚最内层的事务
+    ///
+    /// 连接断开时服务端已经回滚了整个事务：嵌套层数清零，返回原来的错误（提交）或视为回滚成功。
+    fn end(&mut self, conn: &impl TxConnection, commit: bool) -> Result<(), Error> {
+        match (commit, self.depth) {
+            (_, 0) => {
+                log::debug!("{} without an active transaction, ignored", if commit { "commit" } else { "rollback" });
+            }
+            (true, 1) => {
+                if let Err(e) = conn.commit() {
+                    let e = Error::from(format!("commit failed: {}", e));
+                    // 其它错误（如约束检查失败）时事务仍然有效，调用方可以回滚
+                    self.lost_if_broken(conn, &e);
+                    return Err(e);
+                }
+                // 事务已经提交，恢复自动提交失败不能再报告为提交失败
+                self.depth = 0;
+                self.restore_autocommit(conn);
+            }
+            (false, 1) => self.rollback(conn)?,
+            (true, d) => {
+                // 保存点的修改随外层事务一起提交，释放失败不影响结果
+                let sql = format!("RELEASE SAVEPOINT {}", savepoint_name(d - 1));
+                if let Err(e) = Self::execute_sql(conn, &sql) {
+                    log::debug!("{}", e);
+                    if self.lost_if_broken(conn, &e) {
+                        return Err(e);
+                    }
+                }
+                self.depth = d - 1;
+            }
+            (false, d) => {
+                let sql = format!("ROLLBACK TO SAVEPOINT {}", savepoint_name(d - 1));
+                match Self::execute_sql(conn, &sql) {
+                    Ok(()) => self.depth = d - 1,
+                    // 整个事务已经丢失，外层也无需再回滚
+                    Err(e) if self.lost_if_broken(conn, &e) => {}
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// 回滚整个事务，包括所有嵌套的保存点
+    pub fn rollback_all(&mut self, conn: &impl TxConnection) -> Result<(), Error> {
+        if self.depth == 0 {
+            return Ok(());
+        }
+        self.rollback(conn)
+    }
+
+    /// 上一个事务结束后没能恢复自动提交时再试一次，不在事务中执行语句之前调用
+    ///
+    /// 否则之后的语句都会留在一个没有人提交的隐式事务里。
+    pub fn ensure_autocommit(&mut self, conn: &impl TxConnection) -> Result<(), Error> {
+        if self.depth > 0 || !self.autocommit_pending {
+            return Ok(());
+        }
+        conn.set_autocommit(true)
+            .map_err(|e| Error::from(format!("restore autocommit failed: {}", e)))?;
+        self.autocommit_pending = false;
+        Ok(())
+    }
+
+    /// 连接断开时服务端已经回滚了事务：清零嵌套层数并尽量恢复自动提交，返回是否断开
+    fn lost_if_broken(&mut self, conn: &impl TxConnection, error: &Error) -> bool {
+        if !error.is_connection_broken() {
+            return false;
+        }
+        log::warn!("connection broken inside a transaction, rolled back by the server: {}", error);
+        self.depth = 0;
+        self.restore_autocommit(conn);
+        true
+    }
+
+    /// 回滚整个事务并恢复自动提交
+    ///
+    /// 连接已断开时服务端已经回滚了事务，视为成功，之后的操作可以重连后继续。
+    fn rollback(&mut self, conn: &impl TxConnection) -> Result<(), Error> {
+        match conn.rollback() {
+            Ok(()) => {}
+            Err(e) if e.is_connection_broken() => {
+                log::warn!("connection broken inside a transaction, rolled back by the server: {}", e);
+            }
+            Err(e) => return Err(Error::from(format!("rollback failed: {}", e))),
+        }
+        self.depth = 0;
+        self.restore_autocommit(conn);
+        Ok(())
+    }
+
+    fn execute_sql(conn: &impl TxConnection, sql: &str) -> Result<(), Error> {
+        log::debug!("transaction: {}", sql);
+        conn.execute(sql).map_err(|e| Error::from(format!("{} failed: {}", sql, e)))
+    }
+
+    /// 事务已经结束，恢复自动提交失败只记录下来，交给 [`Transaction::ensure_autocommit`] 重试
+    fn restore_autocommit(&mut self, conn: &impl TxConnection) {
+        if let Err(e) = conn.set_autocommit(true) {
+            log::warn!("restore autocommit failed, retry before the next statement: {}", e);
+            self.autocommit_pending = true;
+        }
+    }
+}
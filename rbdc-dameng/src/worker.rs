@@ -0,0 +1,91 @@
+//! 每个连接独占的工作线程
+//!
+//! ODBC 连接句柄不能同时在多个线程上使用。连接建立后由一个专门的线程持有，其它线程只通过命令通道
+//! 把要执行的操作发给它，按发送的顺序逐个执行，结果经 oneshot 通道返回。这样连接句柄从不跨线程，
+//! 也不占用 tokio 的 blocking 线程池。
+//!
+//! 所有 [`Worker`] 句柄释放后命令通道关闭，线程执行完已排队的操作后退出，并释放线程上的状态。
+
+use std::future::Future;
+use std::sync::mpsc;
+use std::thread;
+
+use rbdc::Error;
+use tokio::sync::oneshot;
+
+type Job<S> = Box<dyn FnOnce(&mut S) + Send>;
+
+const WORKER_EXITED: &str = "connection worker thread has exited";
+
+/// 工作线程的句柄，`S` 是只在线程上访问的状态（如 ODBC 连接）
+///
+/// ```
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> Result<(), rbdc::Error> {
+/// use rbdc_dameng::worker::Worker;
+///
+/// let worker = Worker::spawn("counter".to_string(), || Ok(0)).await?;
+/// worker.run(|n: &mut i32| { *n += 1; Ok(()) }).await?;
+/// assert_eq!(worker.run(|n| Ok(*n)).await?, 1);
+/// # Ok(())
+/// # }
+/// ```
+pub struct Worker<S> {
+    jobs: mpsc::Sender<Job<S>>,
+}
+
+impl<S: 'static> Worker<S> {
+    /// 启动工作线程，`init` 在线程上构造状态，失败时线程退出并返回错误
+    pub async fn spawn<I>(name: String, init: I) -> Result<Self, Error>
+    where
+        I: FnOnce() -> Result<S, Error> + Send + 'static,
+    {
+        let (jobs, rx) = mpsc::channel::<Job<S>>();
+        let (ready_tx, ready_rx) = oneshot::channel();
+        thread::Builder::new()
+            .name(name)
+            .spawn(move || {
+                let mut state = match init() {
+                    Ok(state) => state,
+                    Err(e) => {
+                        let _ = ready_tx.send(Err(e));
+                        return;
+                    }
+                };
+                let _ = ready_tx.send(Ok(()));
+                while let Ok(job) = rx.recv() {
+                    job(&mut state);
+                }
+                log::debug!("all handles dropped, worker thread exit");
+            })
+            .map_err(|e| Error::from(format!("spawn worker thread failed: {}", e)))?;
+        ready_rx.await.map_err(|_| Error::from(WORKER_EXITED))??;
+        Ok(Self { jobs })
+    }
+
+    /// 把操作放进队列，不等待结果；用于通过其它通道交付结果的操作（如流式查询）
+    pub fn spawn_job<F>(&self, job: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut S) + Send + 'static,
+    {
+        self.jobs.send(Box::new(job)).map_err(|_| Error::from(WORKER_EXITED))
+    }
+
+    /// 在工作线程上执行操作并返回结果
+    ///
+    /// 调用时立即放进队列，返回的 future 只负责等待结果；线程已退出（如操作 panic）时返回错误。
+    pub fn run<T, F>(&self, job: F) -> impl Future<Output = Result<T, Error>> + Send + 'static
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut S) -> Result<T, Error> + Send + 'static,
+    {
+        let (tx, rx) = oneshot::channel();
+        let sent = self.spawn_job(move |state| {
+            let _ = tx.send(job(state));
+        });
+        async move {
+            sent?;
+            rx.await.map_err(|_| Error::from(WORKER_EXITED))?
+        }
+    }
+}
@@ -0,0 +1,91 @@
+#[cfg(test)]
+mod test {
+    use odbc_api::buffers::BufferDesc;
+    use rbdc_dameng::batch::{bigint_cell, double_cell, text_cell, ColumnKind};
+    use rbs::Value;
+
+    fn ext(name: &'static str, v: Value) -> Value {
+        Value::Ext(name, Box::new(v))
+    }
+
+    #[test]
+    fn test_kind_of() {
+        let samples = [
+            (Value::Null, None),
+            (Value::Bool(true), Some(ColumnKind::Bit)),
+            (Value::I32(1), Some(ColumnKind::BigInt)),
+            (Value::U64(1), Some(ColumnKind::BigInt)),
+            (Value::F32(1.5), Some(ColumnKind::Double)),
+            (Value::String("a".to_string()), Some(ColumnKind::Text)),
+            (Value::Binary(vec![1]), Some(ColumnKind::Binary)),
+            (ext("Date", Value::String("2024-01-02".to_string())), Some(ColumnKind::Date)),
+            (ext("Time", Value::String("12:00:00".to_string())), Some(ColumnKind::Time)),
+            (ext("DateTime", Value::String("2024-01-02 12:00:00".to_string())), Some(ColumnKind::Timestamp)),
+            (ext("Timestamp", Value::I64(0)), Some(ColumnKind::Timestamp)),
+            (ext("Decimal", Value::String("1.5".to_string())), Some(ColumnKind::Text)),
+            (ext("Uuid", Value::String("a".to_string())), Some(ColumnKind::Text)),
+            (ext("Uuid", Value::Binary(vec![1])), Some(ColumnKind::Binary)),
+            (Value::Array(vec![]), Some(ColumnKind::Text)),
+        ];
+        for (value, kind) in samples {
+            assert_eq!(ColumnKind::of(&value), kind, "{}", value);
+        }
+    }
+
+    #[test]
+    fn test_infer_skips_nulls() {
+        let rows = vec![
+            vec![Value::Null, Value::Null],
+            vec![Value::Null, Value::Null],
+            vec![Value::F64(1.0), Value::Null],
+        ];
+        assert_eq!(ColumnKind::infer(&rows, 0), Some(ColumnKind::Double));
+        // 全部为 NULL 的列由 SQLDescribeParam 决定
+        assert_eq!(ColumnKind::infer(&rows, 1), None);
+        assert_eq!(ColumnKind::infer(&[], 0), None);
+    }
+
+    #[test]
+    fn test_buffer_desc() {
+        assert_eq!(ColumnKind::BigInt.buffer_desc(), BufferDesc::I64 { nullable: true });
+        assert_eq!(ColumnKind::Timestamp.buffer_desc(), BufferDesc::Timestamp { nullable: true });
+        // 文本 / 二进制按每批的最大长度增长
+        assert_eq!(ColumnKind::Text.buffer_desc(), BufferDesc::Text { max_str_len: 1 });
+        assert_eq!(ColumnKind::Binary.buffer_desc(), BufferDesc::Binary { length: 1 });
+    }
+
+    #[test]
+    fn test_bigint_cell() {
+        assert_eq!(bigint_cell(&Value::Null), Some(None));
+        assert_eq!(bigint_cell(&Value::I32(-1)), Some(Some(-1)));
+        assert_eq!(bigint_cell(&Value::U32(u32::MAX)), Some(Some(u32::MAX as i64)));
+        assert_eq!(bigint_cell(&Value::U64(i64::MAX as u64)), Some(Some(i64::MAX)));
+        // 超出 BIGINT 范围或类型不兼容
+        assert_eq!(bigint_cell(&Value::U64(u64::MAX)), None);
+        assert_eq!(bigint_cell(&Value::F64(1.0)), None);
+        assert_eq!(bigint_cell(&Value::String("1".to_string())), None);
+    }
+
+    #[test]
+    fn test_double_cell() {
+        assert_eq!(double_cell(&Value::F32(1.5)), Some(Some(1.5)));
+        assert_eq!(double_cell(&Value::I64(2)), Some(Some(2.0)));
+        assert_eq!(double_cell(&Value::Bool(true)), None);
+    }
+
+    #[test]
+    fn test_text_cell() {
+        let text = |v: Value| text_cell(&v).map(|r| r.unwrap().map(|s| s.into_owned()));
+        assert_eq!(text(Value::Null), Some(None));
+        assert_eq!(text(Value::String("达梦".to_string())), Some(Some("达梦".to_string())));
+        assert_eq!(text(Value::I32(7)), Some(Some("7".to_string())));
+        assert_eq!(text(ext("Decimal", Value::String(" 12.30 ".to_string()))), Some(Some("12.30".to_string())));
+        assert_eq!(text(ext("Json", Value::String("{}".to_string()))), Some(Some("{}".to_string())));
+        assert_eq!(
+            text(Value::Array(vec![Value::I32(1), Value::I32(2)])),
+            Some(Some("[1,2]".to_string()))
+        );
+        assert_eq!(text(Value::Binary(vec![1])), None);
+        assert!(text_cell(&ext("Decimal", Value::String("abc".to_string()))).unwrap().is_err());
+    }
+}
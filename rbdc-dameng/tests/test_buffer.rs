@@ -0,0 +1,146 @@
+#[cfg(test)]
+mod test {
+    use std::num::NonZeroUsize;
+
+    use odbc_api::buffers::BufferDesc;
+    use rbdc_dameng::buffer::{column_buffer_desc, exceeds_buffer, row_width, FetchPlan};
+    use rbdc_dameng::common::data_type::DmDataType;
+    use rbdc_dameng::options::TruncationPolicy;
+
+    fn desc(t: DmDataType) -> BufferDesc {
+        column_buffer_desc(t, true, NonZeroUsize::new(100).unwrap())
+    }
+
+    #[test]
+    fn test_native_buffers() {
+        assert_eq!(desc(DmDataType::SmallInt), BufferDesc::I16 { nullable: true });
+        assert_eq!(desc(DmDataType::Integer), BufferDesc::I32 { nullable: true });
+        assert_eq!(desc(DmDataType::BigInt), BufferDesc::I64 { nullable: true });
+        assert_eq!(desc(DmDataType::Real), BufferDesc::F32 { nullable: true });
+        assert_eq!(desc(DmDataType::Double), BufferDesc::F64 { nullable: true });
+        assert_eq!(desc(DmDataType::Float { precision: 53 }), BufferDesc::F64 { nullable: true });
+        assert_eq!(desc(DmDataType::Date), BufferDesc::Date { nullable: true });
+        assert_eq!(desc(DmDataType::Time { precision: 0 }), BufferDesc::Time { nullable: true });
+        assert_eq!(desc(DmDataType::Timestamp { precision: 6 }), BufferDesc::Timestamp { nullable: true });
+        assert_eq!(
+            column_buffer_desc(DmDataType::Integer, false, NonZeroUsize::new(100).unwrap()),
+            BufferDesc::I32 { nullable: false }
+        );
+    }
+
+    #[test]
+    fn test_numeric_buffers() {
+        assert_eq!(desc(DmDataType::Numeric { precision: 9, scale: 0 }), BufferDesc::I32 { nullable: true });
+        assert_eq!(desc(DmDataType::Decimal { precision: 18, scale: 0 }), BufferDesc::I64 { nullable: true });
+        // 有小数位、超过 i64 范围或 NUMBER(*) 都按文本取，保留精度
+        for t in [
+            DmDataType::Numeric { precision: 10, scale: 2 },
+            DmDataType::Numeric { precision: 38, scale: 0 },
+            DmDataType::Numeric { precision: 0, scale: -127 },
+        ] {
+            assert!(matches!(desc(t), BufferDesc::Text { .. }), "{:?}", t);
+        }
+    }
+
+    #[test]
+    fn test_text_fallback() {
+        let len = NonZeroUsize::new(10);
+        // 按 UTF-8 最长 4 字节预留
+        assert_eq!(desc(DmDataType::Varchar { length: len }), BufferDesc::Text { max_str_len: 40 });
+        assert_eq!(desc(DmDataType::Varbinary { length: len }), BufferDesc::Binary { length: 10 });
+        assert_eq!(desc(DmDataType::Time { precision: 3 }), BufferDesc::Text { max_str_len: 12 });
+        // 超过上限或长度未知时按上限分配
+        assert_eq!(
+            desc(DmDataType::Varchar { length: NonZeroUsize::new(8188) }),
+            BufferDesc::Text { max_str_len: 100 }
+        );
+        assert_eq!(desc(DmDataType::LongVarchar { length: None }), BufferDesc::Text { max_str_len: 100 });
+        assert_eq!(desc(DmDataType::LongVarbinary { length: None }), BufferDesc::Binary { length: 100 });
+    }
+
+    #[test]
+    fn test_row_width() {
+        let descs = [
+            BufferDesc::I32 { nullable: false },
+            BufferDesc::I64 { nullable: true },
+            BufferDesc::Text { max_str_len: 10 },
+        ];
+        let indicator = std::mem::size_of::<isize>();
+        assert_eq!(row_width(&descs), 4 + (8 + indicator) + (10 + 1 + indicator));
+        assert_eq!(row_width(&[]), 0);
+    }
+
+    #[test]
+    fn test_exceeds_buffer() {
+        let max = NonZeroUsize::new(100).unwrap();
+        // 定长类型总能放下
+        assert!(!exceeds_buffer(DmDataType::Integer, max));
+        assert!(!exceeds_buffer(DmDataType::Timestamp { precision: 6 }, max));
+        assert!(!exceeds_buffer(DmDataType::Varchar { length: NonZeroUsize::new(25) }, max));
+        assert!(!exceeds_buffer(DmDataType::Varbinary { length: NonZeroUsize::new(100) }, max));
+        // VARCHAR(26) 按 UTF-8 需要 104 字节
+        assert!(exceeds_buffer(DmDataType::Varchar { length: NonZeroUsize::new(26) }, max));
+        assert!(exceeds_buffer(DmDataType::Varbinary { length: NonZeroUsize::new(101) }, max));
+        // TEXT / CLOB / BLOB 长度未知
+        assert!(exceeds_buffer(DmDataType::LongVarchar { length: None }, max));
+        assert!(exceeds_buffer(DmDataType::LongVarbinary { length: None }, max));
+    }
+
+    fn plan(types: &[DmDataType], truncation: TruncationPolicy, block_get_data: bool) -> FetchPlan {
+        FetchPlan::new(types, truncation, NonZeroUsize::new(100).unwrap(), block_get_data)
+    }
+
+    #[test]
+    fn test_fetch_plan_lob_with_normal_columns() {
+        let types = [
+            DmDataType::Integer,
+            DmDataType::Varchar { length: NonZeroUsize::new(20) },
+            DmDataType::Timestamp { precision: 6 },
+            DmDataType::LongVarchar { length: None },
+        ];
+        // 普通列仍然按块取回，只有 CLOB 列逐行读取
+        for truncation in [TruncationPolicy::AutoGrow, TruncationPolicy::Error, TruncationPolicy::WarnAndTruncate] {
+            let plan = plan(&types, truncation, true);
+            assert_eq!(plan.bound, vec![0, 1, 2]);
+            assert_eq!(plan.get_data, vec![3]);
+            assert!(!plan.is_row_by_row());
+        }
+    }
+
+    #[test]
+    fn test_fetch_plan_columns_after_lob() {
+        let types = [
+            DmDataType::BigInt,
+            DmDataType::LongVarbinary { length: None },
+            DmDataType::Integer,
+        ];
+        // SQLGetData 只能读取最后一个绑定列之后的列
+        let plan = plan(&types, TruncationPolicy::AutoGrow, true);
+        assert_eq!(plan.bound, vec![0]);
+        assert_eq!(plan.get_data, vec![1, 2]);
+    }
+
+    #[test]
+    fn test_fetch_plan_long_varchar() {
+        let types = [DmDataType::Integer, DmDataType::Varchar { length: NonZeroUsize::new(26) }];
+        // 只有 AutoGrow 才读取超过 max_str_len 的完整值
+        assert_eq!(plan(&types, TruncationPolicy::AutoGrow, true).get_data, vec![1]);
+        let plan = plan(&types, TruncationPolicy::Error, true);
+        assert_eq!(plan.bound, vec![0, 1]);
+        assert!(plan.get_data.is_empty());
+    }
+
+    #[test]
+    fn test_fetch_plan_row_by_row() {
+        let types = [DmDataType::Integer, DmDataType::LongVarchar { length: None }];
+        // 驱动不支持在块游标上调用 SQLGetData
+        let plan = plan(&types, TruncationPolicy::AutoGrow, false);
+        assert!(plan.is_row_by_row());
+        assert_eq!(plan.get_data, vec![0, 1]);
+
+        // 没有长列时总是按块取回
+        let plan = FetchPlan::new(&[DmDataType::Integer], TruncationPolicy::AutoGrow, NonZeroUsize::new(100).unwrap(), false);
+        assert_eq!(plan.bound, vec![0]);
+        assert!(!plan.is_row_by_row());
+    }
+}
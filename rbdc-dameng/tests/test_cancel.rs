@@ -0,0 +1,67 @@
+#[cfg(test)]
+mod test {
+    use rbdc::Error;
+    use rbdc_dameng::cancel::{cancelled_error, CancelOnDrop, CancelToken};
+    use rbdc_dameng::common::error::DamengErrorExt;
+
+    #[test]
+    fn test_cancel_without_statement() {
+        let token = CancelToken::new();
+        assert!(!token.is_cancelled());
+        // 没有登记的语句时只设置标记
+        token.cancel();
+        assert!(token.is_cancelled());
+    }
+
+    #[test]
+    fn test_check() {
+        let token = CancelToken::new();
+        let lock_timeout = || Error::from("[SQLSTATE HY000, DM -6407] 锁超时");
+        assert!(token.check(lock_timeout()).is_lock_timeout());
+
+        token.cancel();
+        let e = token.check(lock_timeout());
+        assert!(e.is_cancelled());
+        assert_eq!(e.sqlstate().as_deref(), Some("HY008"));
+    }
+
+    #[test]
+    fn test_check_cancelled() {
+        let token = CancelToken::new();
+        assert!(token.check_cancelled().is_ok());
+        token.cancel();
+        assert!(token.check_cancelled().unwrap_err().is_cancelled());
+    }
+
+    #[test]
+    fn test_cancel_on_drop() {
+        let token = CancelToken::new();
+        let guard = CancelOnDrop(token.clone());
+        assert!(!token.is_cancelled());
+        drop(guard);
+        assert!(token.is_cancelled());
+    }
+
+    #[tokio::test]
+    async fn test_dropped_future_cancels() {
+        let token = CancelToken::new();
+        let guard = CancelOnDrop(token.clone());
+        let future = async move {
+            let _guard = guard;
+            std::future::pending::<()>().await
+        };
+        // 超时后 future 被丢弃
+        let result = tokio::time::timeout(std::time::Duration::from_millis(10), future).await;
+        assert!(result.is_err());
+        assert!(token.is_cancelled());
+    }
+
+    #[test]
+    fn test_cancelled_error() {
+        let e = cancelled_error();
+        assert!(e.is_cancelled());
+        assert!(!e.is_timeout());
+        assert!(!e.is_connection_broken());
+        assert!(!e.is_retryable());
+    }
+}
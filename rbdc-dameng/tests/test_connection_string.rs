@@ -0,0 +1,131 @@
+#[cfg(test)]
+mod test {
+    use rbdc_dameng::common::connection_string::{escape_value, get, parse, quote_value};
+
+    fn pairs(v: &[(&str, &str)]) -> Vec<(String, String)> {
+        v.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn test_parse_simple() {
+        assert_eq!(
+            parse("Driver={DM8 ODBC Driver};Server=127.0.0.1:5236;UID=SYSDBA;PWD=SYSDBA001").unwrap(),
+            pairs(&[
+                ("Driver", "DM8 ODBC Driver"),
+                ("Server", "127.0.0.1:5236"),
+                ("UID", "SYSDBA"),
+                ("PWD", "SYSDBA001"),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_empty() {
+        assert!(parse("").unwrap().is_empty());
+        assert!(parse("   ").unwrap().is_empty());
+        assert!(parse(";;; ;").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_parse_trailing_and_repeated_separators() {
+        assert_eq!(parse("UID=a;").unwrap(), pairs(&[("UID", "a")]));
+        assert_eq!(parse(";UID=a;;PWD=b;;").unwrap(), pairs(&[("UID", "a"), ("PWD", "b")]));
+    }
+
+    #[test]
+    fn test_parse_whitespace() {
+        assert_eq!(
+            parse("  UID = a b  ;  PWD =   { c } ; SCHEMA=test ").unwrap(),
+            pairs(&[("UID", "a b"), ("PWD", " c "), ("SCHEMA", "test")])
+        );
+    }
+
+    #[test]
+    fn test_parse_braced_special_chars() {
+        assert_eq!(
+            parse("PWD={a;b=c{d};UID=x").unwrap(),
+            pairs(&[("PWD", "a;b=c{d"), ("UID", "x")])
+        );
+    }
+
+    #[test]
+    fn test_parse_escaped_closing_brace() {
+        assert_eq!(parse("PWD={a}}b}").unwrap(), pairs(&[("PWD", "a}b")]));
+        assert_eq!(parse("PWD={}}}").unwrap(), pairs(&[("PWD", "}")]));
+        assert_eq!(parse("PWD={a}}}}}").unwrap(), pairs(&[("PWD", "a}}")]));
+        assert_eq!(parse("PWD={};UID=x").unwrap(), pairs(&[("PWD", ""), ("UID", "x")]));
+    }
+
+    #[test]
+    fn test_parse_unbraced_value_keeps_equals() {
+        assert_eq!(parse("PWD=a=b==").unwrap(), pairs(&[("PWD", "a=b==")]));
+        assert_eq!(parse("PWD=a}b{c").unwrap(), pairs(&[("PWD", "a}b{c")]));
+        assert_eq!(parse("PWD=;UID=x").unwrap(), pairs(&[("PWD", ""), ("UID", "x")]));
+    }
+
+    #[test]
+    fn test_parse_duplicate_keys_first_wins() {
+        let p = parse("PWD=first;pwd=second;Pwd={third}").unwrap();
+        assert_eq!(p, pairs(&[("PWD", "first")]));
+    }
+
+    #[test]
+    fn test_parse_non_ascii() {
+        assert_eq!(
+            parse("APP_NAME={达梦;应用};PWD=密码").unwrap(),
+            pairs(&[("APP_NAME", "达梦;应用"), ("PWD", "密码")])
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        // 缺少 '='
+        assert!(parse("UID").is_err());
+        assert!(parse("UID=a;PWD").is_err());
+        assert!(parse("UID;PWD=a").is_err());
+        // 空关键字
+        assert!(parse("=a").is_err());
+        assert!(parse(" =a;UID=b").is_err());
+        // 未闭合的 '{'
+        assert!(parse("PWD={abc").is_err());
+        assert!(parse("PWD={abc}};UID=a").is_err());
+        // '}' 之后还有其它字符
+        assert!(parse("PWD={abc}def;UID=a").is_err());
+    }
+
+    #[test]
+    fn test_get_case_insensitive() {
+        let p = parse("Driver={DM8 ODBC Driver};uid=SYSDBA").unwrap();
+        assert_eq!(get(&p, "DRIVER"), Some("DM8 ODBC Driver"));
+        assert_eq!(get(&p, "UID"), Some("SYSDBA"));
+        assert_eq!(get(&p, "Uid"), Some("SYSDBA"));
+        assert_eq!(get(&p, "PWD"), None);
+    }
+
+    #[test]
+    fn test_escape_value() {
+        assert_eq!(escape_value("SYSDBA001"), "SYSDBA001");
+        assert_eq!(escape_value(""), "");
+        assert_eq!(escape_value("a;b"), "{a;b}");
+        assert_eq!(escape_value("a=b"), "{a=b}");
+        assert_eq!(escape_value("a{b"), "{a{b}");
+        assert_eq!(escape_value("a}b"), "{a}}b}");
+        assert_eq!(escape_value(" a"), "{ a}");
+        assert_eq!(escape_value("a "), "{a }");
+        assert_eq!(quote_value("DM8 ODBC Driver"), "{DM8 ODBC Driver}");
+        assert_eq!(quote_value("a}"), "{a}}}");
+    }
+
+    #[test]
+    fn test_escape_round_trip() {
+        let values = [
+            "", "plain", "a;b", "a=b", "{a}", "}}", "{", " lead", "trail ", "a;}b{=c", "中文;}",
+        ];
+        for v in values {
+            let s = format!("PWD={};UID=x", escape_value(v));
+            let p = parse(&s).unwrap();
+            assert_eq!(get(&p, "PWD"), Some(v), "{}", s);
+            assert_eq!(get(&p, "UID"), Some("x"), "{}", s);
+        }
+    }
+}
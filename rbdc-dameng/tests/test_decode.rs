@@ -0,0 +1,144 @@
+#[cfg(test)]
+mod test {
+    use std::num::NonZeroUsize;
+
+    use odbc_api::sys::SqlDataType;
+    use rbdc_dameng::common::data_type::DmDataType;
+    use rbdc_dameng::decode::Decode;
+    use rbdc_dameng::{CellValue, DamengData};
+    use rbs::Value;
+
+    fn all_types() -> Vec<DmDataType> {
+        let len = NonZeroUsize::new(10);
+        vec![
+            DmDataType::Unknown,
+            DmDataType::Char { length: len },
+            DmDataType::WChar { length: len },
+            DmDataType::Numeric { precision: 10, scale: 2 },
+            DmDataType::Decimal { precision: 10, scale: 2 },
+            DmDataType::Integer,
+            DmDataType::SmallInt,
+            DmDataType::Float { precision: 53 },
+            DmDataType::Real,
+            DmDataType::Double,
+            DmDataType::Varchar { length: len },
+            DmDataType::WVarchar { length: len },
+            DmDataType::LongVarchar { length: None },
+            DmDataType::WLongVarchar { length: None },
+            DmDataType::LongVarbinary { length: None },
+            DmDataType::Date,
+            DmDataType::Time { precision: 0 },
+            DmDataType::Timestamp { precision: 6 },
+            DmDataType::BigInt,
+            DmDataType::TinyInt,
+            DmDataType::Bit,
+            DmDataType::Varbinary { length: len },
+            DmDataType::Binary { length: len },
+            DmDataType::Other {
+                data_type: SqlDataType::EXT_GUID,
+                column_size: len,
+                decimal_digits: 0,
+            },
+        ]
+    }
+
+    #[test]
+    fn test_decode_null_every_type() {
+        for t in all_types() {
+            assert_eq!(Value::decode(&DamengData::null(t)).unwrap(), Value::Null, "{:?}", t);
+        }
+    }
+
+    #[test]
+    fn test_decode_null_indicator_wins() {
+        // 指示器为 NULL 时，即便缓冲区里残留了数据也要返回 NULL
+        for t in all_types() {
+            let data = DamengData {
+                data: Some(CellValue::Bytes(b"123".to_vec())),
+                column_type: t,
+                is_sql_null: true,
+            };
+            assert_eq!(Value::decode(&data).unwrap(), Value::Null, "{:?}", t);
+        }
+    }
+
+    #[test]
+    fn test_decode_empty_string_is_not_null() {
+        let len = NonZeroUsize::new(10);
+        for t in [
+            DmDataType::Char { length: len },
+            DmDataType::Varchar { length: len },
+            DmDataType::WVarchar { length: len },
+            DmDataType::LongVarchar { length: None },
+            DmDataType::WLongVarchar { length: None },
+        ] {
+            let v = Value::decode(&DamengData::new(t, vec![])).unwrap();
+            assert_eq!(v, Value::String(String::new()), "{:?}", t);
+        }
+    }
+
+    #[test]
+    fn test_decode_empty_binary_is_not_null() {
+        let len = NonZeroUsize::new(10);
+        for t in [
+            DmDataType::Binary { length: len },
+            DmDataType::Varbinary { length: len },
+            DmDataType::LongVarbinary { length: None },
+        ] {
+            assert_eq!(Value::decode(&DamengData::new(t, vec![])).unwrap(), Value::Binary(vec![]), "{:?}", t);
+            assert_eq!(Value::decode(&DamengData::null(t)).unwrap(), Value::Null, "{:?}", t);
+        }
+    }
+
+    #[test]
+    fn test_decode_values() {
+        assert_eq!(Value::decode(&DamengData::new(DmDataType::Integer, b"42".to_vec())).unwrap(), Value::I32(42));
+        assert_eq!(Value::decode(&DamengData::new(DmDataType::BigInt, b"-7".to_vec())).unwrap(), Value::I64(-7));
+        assert_eq!(
+            Value::decode(&DamengData::new(DmDataType::Varchar { length: None }, "达梦".as_bytes().to_vec())).unwrap(),
+            Value::String("达梦".to_string())
+        );
+    }
+
+    #[test]
+    fn test_decode_native_values() {
+        let native = |t, v| Value::decode(&DamengData::with_value(t, v)).unwrap();
+        assert_eq!(native(DmDataType::SmallInt, CellValue::I16(-3)), Value::I32(-3));
+        assert_eq!(native(DmDataType::Integer, CellValue::I32(42)), Value::I32(42));
+        assert_eq!(native(DmDataType::BigInt, CellValue::I64(1 << 40)), Value::I64(1 << 40));
+        assert_eq!(native(DmDataType::Real, CellValue::F32(1.5)), Value::F32(1.5));
+        assert_eq!(native(DmDataType::Double, CellValue::F64(2.25)), Value::F64(2.25));
+        assert_eq!(
+            native(
+                DmDataType::Time { precision: 0 },
+                CellValue::Time(odbc_api::sys::Time { hour: 8, minute: 5, second: 9 })
+            ),
+            Value::Ext("Time", Box::new(Value::String("08:05:09".to_string())))
+        );
+    }
+
+    #[test]
+    fn test_decode_native_matches_text() {
+        // 原生缓冲区与文本缓冲区解出的值应当一致
+        let ts = odbc_api::sys::Timestamp {
+            year: 2024,
+            month: 2,
+            day: 29,
+            hour: 13,
+            minute: 14,
+            second: 15,
+            fraction: 123_000_000,
+        };
+        let t = DmDataType::Timestamp { precision: 6 };
+        assert_eq!(
+            Value::decode(&DamengData::with_value(t, CellValue::Timestamp(ts))).unwrap(),
+            Value::decode(&DamengData::new(t, b"2024-02-29 13:14:15.123".to_vec())).unwrap()
+        );
+
+        let d = odbc_api::sys::Date { year: 2024, month: 2, day: 29 };
+        assert_eq!(
+            Value::decode(&DamengData::with_value(DmDataType::Date, CellValue::Date(d))).unwrap(),
+            Value::decode(&DamengData::new(DmDataType::Date, b"2024-02-29".to_vec())).unwrap()
+        );
+    }
+}
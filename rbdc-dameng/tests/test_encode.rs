@@ -0,0 +1,161 @@
+#[cfg(test)]
+mod test {
+    use odbc_api::handles::{CData, HasDataType};
+    use odbc_api::sys::{CDataType, Timestamp};
+    use odbc_api::DataType;
+    use rbdc::types::date::Date;
+    use rbdc::types::datetime::DateTime;
+    use rbdc::types::decimal::Decimal;
+    use rbdc::types::timestamp::Timestamp as RbdcTimestamp;
+    use rbdc_dameng::encode::{encode_params_with, Encode};
+    use rbs::Value;
+    use std::str::FromStr;
+
+    fn text_of(p: &dyn CData) -> String {
+        let len = unsafe { *p.indicator_ptr() } as usize;
+        let bytes = unsafe { std::slice::from_raw_parts(p.value_ptr() as *const u8, len) };
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[test]
+    fn test_encode_numbers() {
+        let p = Value::I32(7).encode(0).unwrap();
+        assert_eq!(p.cdata_type(), CDataType::SLong);
+        assert_eq!(p.data_type(), DataType::Integer);
+        assert_eq!(unsafe { *(p.value_ptr() as *const i32) }, 7);
+
+        let p = Value::I64(1 << 40).encode(0).unwrap();
+        assert_eq!(p.cdata_type(), CDataType::SBigInt);
+        assert_eq!(p.data_type(), DataType::BigInt);
+        assert_eq!(unsafe { *(p.value_ptr() as *const i64) }, 1 << 40);
+
+        let p = Value::U32(u32::MAX).encode(0).unwrap();
+        assert_eq!(p.data_type(), DataType::BigInt);
+        assert_eq!(unsafe { *(p.value_ptr() as *const i64) }, u32::MAX as i64);
+
+        let p = Value::F64(1.5).encode(0).unwrap();
+        assert_eq!(p.cdata_type(), CDataType::Double);
+        assert_eq!(p.data_type(), DataType::Double);
+        assert_eq!(unsafe { *(p.value_ptr() as *const f64) }, 1.5);
+
+        let p = Value::U64(u64::MAX).encode(0).unwrap();
+        assert_eq!(p.cdata_type(), CDataType::Char);
+        assert_eq!(p.data_type(), DataType::Decimal { precision: 20, scale: 0 });
+        assert_eq!(text_of(p.as_ref()), u64::MAX.to_string());
+    }
+
+    #[test]
+    fn test_encode_decimal() {
+        let p = Value::from(Decimal::from_str("-12345.678").unwrap()).encode(0).unwrap();
+        assert_eq!(p.cdata_type(), CDataType::Char);
+        assert_eq!(p.data_type(), DataType::Decimal { precision: 8, scale: 3 });
+        assert_eq!(text_of(p.as_ref()), "-12345.678");
+
+        let p = Value::from(Decimal::from_str("0.0001").unwrap()).encode(0).unwrap();
+        assert_eq!(p.data_type(), DataType::Decimal { precision: 4, scale: 4 });
+        assert_eq!(text_of(p.as_ref()), "0.0001");
+
+        let p = Value::from(Decimal::from_str("1E+3").unwrap()).encode(0).unwrap();
+        assert_eq!(p.data_type(), DataType::Decimal { precision: 4, scale: 0 });
+        assert_eq!(text_of(p.as_ref()), "1000");
+    }
+
+    #[test]
+    fn test_encode_date_time() {
+        let p = Value::from(Date::from_str("2024-02-29").unwrap()).encode(0).unwrap();
+        assert_eq!(p.cdata_type(), CDataType::TypeDate);
+        assert_eq!(p.data_type(), DataType::Date);
+        let d = unsafe { &*(p.value_ptr() as *const odbc_api::sys::Date) };
+        assert_eq!((d.year, d.month, d.day), (2024, 2, 29));
+
+        let offset = fastdate::offset_sec();
+        let dt = fastdate::DateTime::from_str("2024-02-29T13:14:15.123456789Z")
+            .unwrap()
+            .set_offset(offset);
+        let expect = (dt.year() as i16, dt.mon() as u16, dt.day() as u16, dt.hour() as u16);
+
+        let p = Value::from(DateTime(dt.clone())).encode(0).unwrap();
+        assert_eq!(p.cdata_type(), CDataType::TypeTimestamp);
+        assert_eq!(p.data_type(), DataType::Timestamp { precision: 6 });
+        let ts = unsafe { &*(p.value_ptr() as *const Timestamp) };
+        assert_eq!((ts.year, ts.month, ts.day, ts.hour), expect);
+        assert_eq!((ts.minute, ts.second, ts.fraction), (14, 15, 123456000));
+
+        let p = Value::from(RbdcTimestamp(dt.unix_timestamp_millis())).encode(0).unwrap();
+        assert_eq!(p.cdata_type(), CDataType::TypeTimestamp);
+        let ts = unsafe { &*(p.value_ptr() as *const Timestamp) };
+        assert_eq!((ts.year, ts.month, ts.day, ts.hour), expect);
+        assert_eq!((ts.minute, ts.second, ts.fraction), (14, 15, 123000000));
+    }
+
+    #[test]
+    fn test_encode_binary_and_text() {
+        let p = Value::Binary(vec![0, 1, 2, 255]).encode(0).unwrap();
+        assert_eq!(p.cdata_type(), CDataType::Binary);
+        assert!(matches!(p.data_type(), DataType::Varbinary { .. } | DataType::LongVarbinary { .. }));
+        assert_eq!(unsafe { *p.indicator_ptr() }, 4);
+
+        let p = Value::String("达梦".to_string()).encode(0).unwrap();
+        assert_eq!(p.cdata_type(), CDataType::Char);
+        assert_eq!(text_of(p.as_ref()), "达梦");
+    }
+
+    const SQL_NULL_DATA: isize = -1;
+
+    #[test]
+    fn test_encode_null_fallback() {
+        let p = Value::Null.encode(0).unwrap();
+        assert_eq!(unsafe { *p.indicator_ptr() }, SQL_NULL_DATA);
+        assert_eq!(p.cdata_type(), CDataType::Char);
+        assert!(matches!(p.data_type(), DataType::Varchar { .. }));
+
+        // 不能再被当成字符串 'NULL'
+        let p = Value::Null.encode(0).unwrap();
+        assert_ne!(unsafe { *p.indicator_ptr() }, 4);
+    }
+
+    #[test]
+    fn test_encode_null_described() {
+        let params = vec![Value::I32(1), Value::Null, Value::Null, Value::Null];
+        let mut described = vec![];
+        let encoded = encode_params_with(params, |n| {
+            described.push(n);
+            match n {
+                2 => Some(DataType::Integer),
+                3 => Some(DataType::Varbinary { length: std::num::NonZeroUsize::new(16) }),
+                _ => Some(DataType::Timestamp { precision: 6 }),
+            }
+        })
+        .unwrap();
+        // 只对 NULL 参数调用 describe
+        assert_eq!(described, vec![2, 3, 4]);
+
+        assert_eq!(encoded[0].data_type(), DataType::Integer);
+        assert_eq!(unsafe { *(encoded[0].value_ptr() as *const i32) }, 1);
+
+        assert_eq!(unsafe { *encoded[1].indicator_ptr() }, SQL_NULL_DATA);
+        assert_eq!(encoded[1].data_type(), DataType::Integer);
+
+        assert_eq!(unsafe { *encoded[2].indicator_ptr() }, SQL_NULL_DATA);
+        assert_eq!(encoded[2].cdata_type(), CDataType::Binary);
+
+        assert_eq!(unsafe { *encoded[3].indicator_ptr() }, SQL_NULL_DATA);
+        assert_eq!(encoded[3].data_type(), DataType::Timestamp { precision: 6 });
+    }
+
+    #[test]
+    fn test_encode_null_describe_unsupported() {
+        let mut calls = 0;
+        let encoded = encode_params_with(vec![Value::Null, Value::Null], |_| {
+            calls += 1;
+            None
+        })
+        .unwrap();
+        // 第一次失败后不再尝试
+        assert_eq!(calls, 1);
+        for p in &encoded {
+            assert_eq!(unsafe { *p.indicator_ptr() }, SQL_NULL_DATA);
+            assert!(matches!(p.data_type(), DataType::Varchar { .. }));
+        }
+    }
+}
@@ -0,0 +1,157 @@
+#[cfg(test)]
+mod test {
+    use odbc_api::handles::{Record, State};
+    use rbdc_dameng::common::error::{classify, odbc_error, DamengError, DamengErrorExt, DiagRecord, Error, ErrorKind};
+
+    fn record(sqlstate: &str, native_code: i32, message: &str) -> DiagRecord {
+        DiagRecord {
+            sqlstate: sqlstate.to_string(),
+            native_code,
+            message: message.to_string(),
+        }
+    }
+
+    fn diagnostics(state: &[u8; 5], native_error: i32, message: &str) -> odbc_api::Error {
+        odbc_api::Error::Diagnostics {
+            record: Record {
+                state: State(*state),
+                native_error,
+                message: message.as_bytes().to_vec(),
+            },
+            function: "SQLExecute",
+        }
+    }
+
+    #[test]
+    fn test_from_odbc_diagnostics() {
+        let e = DamengError::from(diagnostics(b"23000", -6602, "违反表[T]唯一性约束.\n"));
+        assert_eq!(e.sqlstate(), Some("23000"));
+        assert_eq!(e.native_code(), Some(-6602));
+        assert_eq!(e.message(), "违反表[T]唯一性约束.");
+        assert_eq!(e.records(), &[record("23000", -6602, "违反表[T]唯一性约束.")]);
+        assert_eq!(e.to_string(), "[SQLSTATE 23000, DM -6602] 违反表[T]唯一性约束.");
+    }
+
+    #[test]
+    fn test_from_odbc_without_diagnostics() {
+        let e = DamengError::from(odbc_api::Error::NoDiagnostics { function: "SQLFetch" });
+        assert!(matches!(e, DamengError::Other(_)));
+        assert_eq!(e.sqlstate(), None);
+        assert_eq!(e.native_code(), None);
+        assert!(e.records().is_empty());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let e = DamengError::from_records(vec![
+            record("42S02", -2106, "无效的表或视图名[T]"),
+            record("01000", 0, "第二行\n多行信息"),
+        ]);
+        let rbdc_error: Error = e.clone().into();
+        assert_eq!(DamengError::from_error(&rbdc_error), e);
+        assert_eq!(rbdc_error.dameng(), e);
+    }
+
+    #[test]
+    fn test_parse_with_context() {
+        let e = Error::from(format!(
+            "commit failed: {}",
+            DamengError::from(diagnostics(b"40001", -6403, "deadlock"))
+        ));
+        assert_eq!(e.sqlstate().as_deref(), Some("40001"));
+        assert_eq!(e.native_code(), Some(-6403));
+        assert_eq!(e.dameng().message(), "deadlock");
+
+        let e = odbc_error(diagnostics(b"08S01", -70028, "lost"));
+        assert_eq!(e.native_code(), Some(-70028));
+    }
+
+    #[test]
+    fn test_parse_other() {
+        let e = Error::from("query returned no rows");
+        assert_eq!(e.dameng(), DamengError::Other("query returned no rows".to_string()));
+        assert_eq!(e.sqlstate(), None);
+        // 格式不完整时不当作诊断记录
+        let e = Error::from("[SQLSTATE 23000] missing code");
+        assert!(matches!(e.dameng(), DamengError::Other(_)));
+        assert_eq!(DamengError::from_records(vec![]).sqlstate(), None);
+    }
+
+    /// 样例诊断记录和期望的类别
+    const SAMPLES: &[(&[u8; 5], i32, &str, ErrorKind)] = &[
+        (b"23000", -6602, "违反表[T]唯一性约束.", ErrorKind::UniqueViolation),
+        (b"23000", -6625, "违反引用约束[FK_T_P].", ErrorKind::ForeignKeyViolation),
+        (b"23000", -6626, "违反引用约束[FK_T_P].", ErrorKind::ForeignKeyViolation),
+        (b"40001", -6403, "死锁", ErrorKind::Deadlock),
+        (b"HY000", -6407, "锁超时", ErrorKind::LockTimeout),
+        (b"08S01", -70019, "网络通信异常", ErrorKind::ConnectionBroken),
+        (b"08001", -70028, "创建SOCKET连接失败", ErrorKind::ConnectionBroken),
+        (b"HY000", -6001, "网络通信异常", ErrorKind::ConnectionBroken),
+        // 只靠 SQLSTATE 识别
+        (b"23505", 0, "duplicate key", ErrorKind::UniqueViolation),
+        (b"23503", 0, "foreign key", ErrorKind::ForeignKeyViolation),
+        (b"40001", 0, "serialization failure", ErrorKind::Deadlock),
+        (b"08003", 0, "connection not open", ErrorKind::ConnectionBroken),
+        (b"HYT01", 0, "connection timeout", ErrorKind::ConnectionBroken),
+        (b"HYT00", 0, "timeout expired", ErrorKind::Timeout),
+        (b"HY008", 0, "operation canceled", ErrorKind::Cancelled),
+        (b"42S22", 0, "column not found", ErrorKind::SchemaChanged),
+        (b"42S02", -2106, "无效的表或视图名[T]", ErrorKind::SchemaChanged),
+        (b"42S22", -2111, "无效的列名[C]", ErrorKind::SchemaChanged),
+        // 其它约束错误不归类
+        (b"23000", -6609, "违反列[C]非空约束", ErrorKind::Other),
+    ];
+
+    #[test]
+    fn test_classify_samples() {
+        for (state, native_error, message, kind) in SAMPLES {
+            let e = DamengError::from(diagnostics(state, *native_error, message));
+            assert_eq!(e.kind(), *kind, "{}", e);
+            assert_eq!(e.is_unique_violation(), *kind == ErrorKind::UniqueViolation, "{}", e);
+            assert_eq!(e.is_foreign_key_violation(), *kind == ErrorKind::ForeignKeyViolation, "{}", e);
+            assert_eq!(e.is_deadlock(), *kind == ErrorKind::Deadlock, "{}", e);
+            assert_eq!(e.is_lock_timeout(), *kind == ErrorKind::LockTimeout, "{}", e);
+            assert_eq!(e.is_connection_broken(), *kind == ErrorKind::ConnectionBroken, "{}", e);
+            assert_eq!(e.is_timeout(), *kind == ErrorKind::Timeout, "{}", e);
+            assert_eq!(e.is_cancelled(), *kind == ErrorKind::Cancelled, "{}", e);
+            assert_eq!(e.is_schema_changed(), *kind == ErrorKind::SchemaChanged, "{}", e);
+
+            // 经过 rbdc::Error 之后结果不变
+            let rbdc_error: Error = e.clone().into();
+            assert_eq!(rbdc_error.dameng().kind(), *kind, "{}", e);
+        }
+    }
+
+    #[test]
+    fn test_retryable() {
+        for (kind, retryable) in [
+            (ErrorKind::UniqueViolation, false),
+            (ErrorKind::ForeignKeyViolation, false),
+            (ErrorKind::Deadlock, true),
+            (ErrorKind::LockTimeout, true),
+            (ErrorKind::ConnectionBroken, true),
+            (ErrorKind::Timeout, false),
+            (ErrorKind::Cancelled, false),
+            (ErrorKind::SchemaChanged, false),
+            (ErrorKind::Other, false),
+        ] {
+            let (state, native_error, message, _) = SAMPLES.iter().find(|s| s.3 == kind).unwrap();
+            let e = odbc_error(diagnostics(state, *native_error, message));
+            assert_eq!(e.is_retryable(), retryable, "{:?}", kind);
+        }
+        assert!(!Error::from("query returned no rows").is_retryable());
+    }
+
+    #[test]
+    fn test_classify_precedence() {
+        // 错误码优先于 SQLSTATE
+        assert_eq!(classify("08S01", -6602), ErrorKind::UniqueViolation);
+        assert_eq!(classify("08S01", 0), ErrorKind::ConnectionBroken);
+        // 取第一条能识别的记录
+        let e = DamengError::from_records(vec![
+            DiagRecord { sqlstate: "01000".to_string(), native_code: 0, message: "warning".to_string() },
+            DiagRecord { sqlstate: "40001".to_string(), native_code: -6403, message: "死锁".to_string() },
+        ]);
+        assert!(e.is_deadlock());
+    }
+}
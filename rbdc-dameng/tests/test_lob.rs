@@ -0,0 +1,121 @@
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+    use std::num::NonZeroUsize;
+
+    use futures_util::StreamExt;
+    use odbc_api::handles::HasDataType;
+    use odbc_api::parameter::Blob;
+    use odbc_api::sys::{CDataType, SqlDataType};
+    use odbc_api::DataType;
+    use rbdc_dameng::buffer::{is_binary, is_lob};
+    use rbdc_dameng::common::data_type::DmDataType;
+    use rbdc_dameng::decode::Decode;
+    use rbdc_dameng::lob::{LobParam, LobReader, Param};
+    use rbdc_dameng::DamengData;
+    use rbs::Value;
+    use tokio::io::AsyncReadExt;
+    use tokio::sync::mpsc;
+    use tokio_util::compat::FuturesAsyncReadCompatExt;
+
+    fn reader(chunks: Vec<Result<Vec<u8>, rbdc::Error>>) -> LobReader {
+        let (tx, rx) = mpsc::channel(chunks.len().max(1));
+        for chunk in chunks {
+            tx.try_send(chunk).unwrap();
+        }
+        LobReader::new(rx)
+    }
+
+    #[test]
+    fn test_is_lob() {
+        assert!(is_lob(DmDataType::LongVarchar { length: None }));
+        assert!(is_lob(DmDataType::LongVarbinary { length: None }));
+        assert!(is_lob(DmDataType::Other {
+            data_type: SqlDataType::EXT_LONG_VAR_BINARY,
+            column_size: None,
+            decimal_digits: 0,
+        }));
+        assert!(!is_lob(DmDataType::Varchar { length: NonZeroUsize::new(10) }));
+        assert!(!is_lob(DmDataType::Varbinary { length: NonZeroUsize::new(10) }));
+    }
+
+    #[test]
+    fn test_is_binary() {
+        assert!(is_binary(DmDataType::Binary { length: NonZeroUsize::new(4) }));
+        assert!(is_binary(DmDataType::LongVarbinary { length: None }));
+        assert!(!is_binary(DmDataType::LongVarchar { length: None }));
+    }
+
+    #[tokio::test]
+    async fn test_reader_stream() {
+        let mut r = reader(vec![Ok(b"ab".to_vec()), Ok(b"cd".to_vec())]);
+        assert_eq!(r.next().await.unwrap().unwrap(), b"ab");
+        assert_eq!(r.next().await.unwrap().unwrap(), b"cd");
+        assert!(r.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_reader_async_read() {
+        let mut r = reader(vec![Ok(b"hello ".to_vec()), Ok(b"world".to_vec())]);
+        let mut head = [0u8; 3];
+        (&mut r).compat().read_exact(&mut head).await.unwrap();
+        assert_eq!(&head, b"hel");
+        // 读剩的部分先从 Stream 交出
+        assert_eq!(r.next().await.unwrap().unwrap(), b"lo ");
+        let mut rest = vec![];
+        r.compat().read_to_end(&mut rest).await.unwrap();
+        assert_eq!(rest, b"world");
+    }
+
+    #[tokio::test]
+    async fn test_reader_error() {
+        let r = reader(vec![Ok(b"ab".to_vec()), Err(rbdc::Error::from("broken"))]);
+        let mut buf = vec![];
+        let err = r.compat().read_to_end(&mut buf).await.unwrap_err();
+        assert!(err.to_string().contains("broken"));
+        assert_eq!(buf, b"ab");
+    }
+
+    #[test]
+    fn test_lob_param_batches() {
+        let mut p = LobParam::binary(Cursor::new(vec![1u8, 2, 3]));
+        assert_eq!(p.c_data_type(), CDataType::Binary);
+        assert_eq!(p.data_type(), DataType::LongVarbinary { length: None });
+        assert_eq!(p.size_hint(), None);
+        assert_eq!(p.next_batch().unwrap(), Some(&[1u8, 2, 3][..]));
+        assert_eq!(p.next_batch().unwrap(), None);
+
+        let mut t = LobParam::text(Cursor::new(b"text".to_vec()));
+        assert_eq!(t.c_data_type(), CDataType::Char);
+        assert_eq!(t.data_type(), DataType::LongVarchar { length: None });
+        assert_eq!(t.next_batch().unwrap(), Some(&b"text"[..]));
+        assert_eq!(t.next_batch().unwrap(), None);
+    }
+
+    #[test]
+    fn test_lob_param_from_path() {
+        let path = std::env::temp_dir().join("rbdc_dameng_test_lob_param.bin");
+        std::fs::write(&path, [0u8; 5]).unwrap();
+        let p = LobParam::from_path(&path).unwrap();
+        assert_eq!(p.size_hint(), Some(5));
+        assert_eq!(p.data_type(), DataType::LongVarbinary { length: NonZeroUsize::new(5) });
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_param_from() {
+        assert!(matches!(Param::from(Value::I32(1)), Param::Value(Value::I32(1))));
+        assert!(matches!(Param::from(LobParam::binary(Cursor::new(vec![]))), Param::Lob(_)));
+    }
+
+    #[test]
+    fn test_decode_long_binary() {
+        let t = DmDataType::Other {
+            data_type: SqlDataType::EXT_LONG_VAR_BINARY,
+            column_size: None,
+            decimal_digits: 0,
+        };
+        let data = DamengData::new(t, vec![0xff, 0x00]);
+        assert_eq!(Value::decode(&data).unwrap(), Value::Binary(vec![0xff, 0x00]));
+    }
+}
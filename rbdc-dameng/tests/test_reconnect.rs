@@ -0,0 +1,212 @@
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+    use std::time::Duration;
+
+    use rbdc::Error;
+    use rbdc_dameng::options::{DamengConnectOptions, ReconnectPolicy};
+    use rbdc_dameng::reconnect::with_reconnect;
+
+    /// 模拟的连接，`alive` 为 `false` 时所有操作都返回连接断开的错误
+    struct FakeConn {
+        id: u32,
+        alive: bool,
+    }
+
+    impl FakeConn {
+        fn query(&self) -> Result<u32, Error> {
+            if self.alive {
+                Ok(self.id)
+            } else {
+                Err(broken())
+            }
+        }
+    }
+
+    fn broken() -> Error {
+        Error::from("exec failed: [SQLSTATE 08S01, DM -70019] 网络通信异常")
+    }
+
+    fn policy(max_retries: u32) -> ReconnectPolicy {
+        ReconnectPolicy::new(max_retries, Duration::ZERO, Duration::ZERO)
+    }
+
+    #[test]
+    fn test_reconnect_and_retry() {
+        let connects = Cell::new(0);
+        let mut conn = FakeConn { id: 0, alive: false };
+        let result = with_reconnect(
+            &mut conn,
+            &policy(3),
+            |_| true,
+            || {
+                connects.set(connects.get() + 1);
+                Ok(FakeConn { id: connects.get(), alive: true })
+            },
+            |c| c.query(),
+        );
+        assert_eq!(result.unwrap(), 1);
+        assert_eq!(connects.get(), 1);
+        assert_eq!(conn.id, 1);
+    }
+
+    #[test]
+    fn test_no_reconnect_when_alive() {
+        let connects = Cell::new(0);
+        let mut conn = FakeConn { id: 7, alive: true };
+        let result = with_reconnect(
+            &mut conn,
+            &policy(3),
+            |_| true,
+            || {
+                connects.set(connects.get() + 1);
+                Ok(FakeConn { id: 0, alive: true })
+            },
+            |c| c.query(),
+        );
+        assert_eq!(result.unwrap(), 7);
+        assert_eq!(connects.get(), 0);
+    }
+
+    #[test]
+    fn test_retries_exhausted() {
+        let connects = Cell::new(0);
+        let mut conn = FakeConn { id: 0, alive: false };
+        let result = with_reconnect(
+            &mut conn,
+            &policy(3),
+            |_| true,
+            || {
+                connects.set(connects.get() + 1);
+                // 数据库还没恢复，新连接上的操作依旧失败
+                Ok(FakeConn { id: connects.get(), alive: false })
+            },
+            |c| c.query(),
+        );
+        assert_eq!(result.unwrap_err().to_string(), broken().to_string());
+        assert_eq!(connects.get(), 3);
+    }
+
+    #[test]
+    fn test_connect_failure_retried() {
+        let connects = Cell::new(0);
+        let mut conn = FakeConn { id: 0, alive: false };
+        let result = with_reconnect(
+            &mut conn,
+            &policy(3),
+            |_| true,
+            || {
+                connects.set(connects.get() + 1);
+                if connects.get() < 3 {
+                    Err(Error::from("connect failed: [SQLSTATE 08001, DM -70028] 创建SOCKET连接失败"))
+                } else {
+                    Ok(FakeConn { id: connects.get(), alive: true })
+                }
+            },
+            |c| c.query(),
+        );
+        assert_eq!(result.unwrap(), 3);
+        assert_eq!(connects.get(), 3);
+    }
+
+    #[test]
+    fn test_connect_failure_not_broken() {
+        let connects = Cell::new(0);
+        let mut conn = FakeConn { id: 0, alive: false };
+        let result = with_reconnect(
+            &mut conn,
+            &policy(3),
+            |_| true,
+            || {
+                connects.set(connects.get() + 1);
+                Err(Error::from("connect failed: [SQLSTATE 28000, DM -2501] 用户名或密码错误"))
+            },
+            |c| c.query(),
+        );
+        assert!(result.unwrap_err().to_string().contains("-2501"));
+        assert_eq!(connects.get(), 1);
+    }
+
+    #[test]
+    fn test_other_error_not_retried() {
+        let connects = Cell::new(0);
+        let calls = Cell::new(0);
+        let mut conn = FakeConn { id: 0, alive: true };
+        let result: Result<(), Error> = with_reconnect(
+            &mut conn,
+            &policy(3),
+            |_| true,
+            || {
+                connects.set(connects.get() + 1);
+                Ok(FakeConn { id: 1, alive: true })
+            },
+            |_| {
+                calls.set(calls.get() + 1);
+                Err(Error::from("[SQLSTATE 23000, DM -6602] 违反表[T]唯一性约束."))
+            },
+        );
+        assert!(result.is_err());
+        assert_eq!(calls.get(), 1);
+        assert_eq!(connects.get(), 0);
+    }
+
+    #[test]
+    fn test_not_retried_inside_transaction() {
+        let connects = Cell::new(0);
+        let mut conn = FakeConn { id: 0, alive: false };
+        let result = with_reconnect(
+            &mut conn,
+            &policy(3),
+            // 在事务中
+            |_| false,
+            || {
+                connects.set(connects.get() + 1);
+                Ok(FakeConn { id: 1, alive: true })
+            },
+            |c| c.query(),
+        );
+        assert_eq!(result.unwrap_err().to_string(), broken().to_string());
+        assert_eq!(connects.get(), 0);
+        assert_eq!(conn.id, 0);
+    }
+
+    #[test]
+    fn test_disabled() {
+        let connects = Cell::new(0);
+        let mut conn = FakeConn { id: 0, alive: false };
+        let result = with_reconnect(
+            &mut conn,
+            &ReconnectPolicy::disabled(),
+            |_| true,
+            || {
+                connects.set(connects.get() + 1);
+                Ok(FakeConn { id: 1, alive: true })
+            },
+            |c| c.query(),
+        );
+        assert!(result.is_err());
+        assert_eq!(connects.get(), 0);
+    }
+
+    #[test]
+    fn test_backoff() {
+        let policy = ReconnectPolicy::new(10, Duration::from_millis(100), Duration::from_secs(1));
+        assert_eq!(policy.backoff(0), Duration::from_millis(100));
+        assert_eq!(policy.backoff(1), Duration::from_millis(200));
+        assert_eq!(policy.backoff(3), Duration::from_millis(800));
+        assert_eq!(policy.backoff(4), Duration::from_secs(1));
+        assert_eq!(policy.backoff(63), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_options_default_policy() {
+        let opt = DamengConnectOptions::new().reconnect(ReconnectPolicy::disabled());
+        assert_eq!(opt.reconnect.max_retries, 0);
+
+        // 旧的序列化结果里没有这个字段
+        let mut json = serde_json::to_value(&opt).unwrap();
+        json.as_object_mut().unwrap().remove("reconnect");
+        let opt: DamengConnectOptions = serde_json::from_value(json).unwrap();
+        assert_eq!(opt.reconnect, ReconnectPolicy::default());
+    }
+}
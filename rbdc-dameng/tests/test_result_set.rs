@@ -0,0 +1,154 @@
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use rbdc::db::Row;
+    use rbdc::Error;
+    use rbdc_dameng::common::data_type::DmDataType;
+    use rbdc_dameng::results::{collect_results, ResultSource};
+    use rbdc_dameng::{DamengColumn, DamengData, DamengRow, ResultSet};
+    use rbs::Value;
+
+    /// 模拟语句返回的一个结果
+    enum Part {
+        /// 有给定行数的结果集
+        Query(usize),
+        /// 影响了给定行数的非查询结果
+        Affected(u64),
+    }
+
+    /// 依次返回 `parts`，`closes_after` 之后读取结果集时游标关闭
+    struct FakeSource {
+        parts: Vec<Part>,
+        current: usize,
+        closes_after: Option<usize>,
+        more_results_calls: usize,
+    }
+
+    impl FakeSource {
+        fn new(parts: Vec<Part>) -> Self {
+            Self { parts, current: 0, closes_after: None, more_results_calls: 0 }
+        }
+    }
+
+    fn int_column() -> Arc<Vec<DamengColumn>> {
+        Arc::new(vec![DamengColumn {
+            name: "id".to_string(),
+            column_type: DmDataType::Integer,
+            nullability: true,
+        }])
+    }
+
+    impl ResultSource for FakeSource {
+        fn num_result_cols(&mut self) -> Result<usize, Error> {
+            Ok(match self.parts[self.current] {
+                Part::Query(_) => 1,
+                Part::Affected(_) => 0,
+            })
+        }
+
+        fn row_count(&mut self) -> u64 {
+            match self.parts[self.current] {
+                Part::Affected(n) => n,
+                Part::Query(_) => panic!("row_count on a result set"),
+            }
+        }
+
+        fn read_result_set(&mut self) -> Result<(ResultSet, bool), Error> {
+            let Part::Query(n) = self.parts[self.current] else {
+                panic!("read_result_set on a row count");
+            };
+            let columns = int_column();
+            let rows = (0..n)
+                .map(|_| DamengRow {
+                    columns: columns.clone(),
+                    datas: vec![DamengData::null(DmDataType::Integer)],
+                })
+                .collect();
+            let open = self.closes_after != Some(self.current);
+            Ok((ResultSet { columns, rows, rows_affected: None }, open))
+        }
+
+        fn more_results(&mut self) -> Result<bool, Error> {
+            self.more_results_calls += 1;
+            if self.current + 1 < self.parts.len() {
+                self.current += 1;
+                Ok(true)
+            } else {
+                Ok(false)
+            }
+        }
+    }
+
+    fn summary(results: &[ResultSet]) -> Vec<(bool, usize, Option<u64>)> {
+        results.iter().map(|r| (r.is_query(), r.rows.len(), r.rows_affected)).collect()
+    }
+
+    #[test]
+    fn test_affected() {
+        let result = ResultSet::affected(3);
+        assert!(!result.is_query());
+        assert_eq!(result.rows_affected, Some(3));
+        assert!(result.columns.is_empty());
+        assert!(result.rows.is_empty());
+    }
+
+    #[test]
+    fn test_query() {
+        let columns = Arc::new(vec![DamengColumn {
+            name: "id".to_string(),
+            column_type: DmDataType::Integer,
+            nullability: true,
+        }]);
+        let mut result = ResultSet {
+            columns: columns.clone(),
+            rows: vec![DamengRow {
+                columns,
+                datas: vec![DamengData::null(DmDataType::Integer)],
+            }],
+            rows_affected: None,
+        };
+        assert!(result.is_query());
+        assert_eq!(result.rows[0].meta_data().column_name(0), "id");
+        assert_eq!(result.rows[0].get(0).unwrap(), Value::Null);
+    }
+
+    #[test]
+    fn test_collect_multiple_result_sets() {
+        let mut source = FakeSource::new(vec![Part::Query(2), Part::Query(1)]);
+        let results = collect_results(&mut source).unwrap();
+        assert_eq!(summary(&results), [(true, 2, None), (true, 1, None)]);
+        assert_eq!(results[0].rows[1].datas.len(), 1);
+        assert_eq!(source.more_results_calls, 2);
+    }
+
+    #[test]
+    fn test_collect_row_counts_between_result_sets() {
+        // 存储过程中查询之间夹着 INSERT / UPDATE
+        let mut source = FakeSource::new(vec![Part::Query(1), Part::Affected(3), Part::Affected(0), Part::Query(2)]);
+        let results = collect_results(&mut source).unwrap();
+        assert_eq!(
+            summary(&results),
+            [(true, 1, None), (false, 0, Some(3)), (false, 0, Some(0)), (true, 2, None)]
+        );
+    }
+
+    #[test]
+    fn test_collect_empty_trailing_result_set() {
+        // 最后一个查询没有行，仍然作为结果集返回
+        let mut source = FakeSource::new(vec![Part::Affected(1), Part::Query(0)]);
+        let results = collect_results(&mut source).unwrap();
+        assert_eq!(summary(&results), [(false, 0, Some(1)), (true, 0, None)]);
+        assert!(results[1].rows.is_empty());
+        assert_eq!(results[1].columns.len(), 1);
+    }
+
+    #[test]
+    fn test_collect_stops_when_cursor_closed() {
+        let mut source = FakeSource::new(vec![Part::Query(1), Part::Query(1)]);
+        source.closes_after = Some(0);
+        let results = collect_results(&mut source).unwrap();
+        assert_eq!(summary(&results), [(true, 1, None)]);
+        assert_eq!(source.more_results_calls, 0);
+    }
+}
@@ -0,0 +1,121 @@
+#[cfg(test)]
+mod test {
+    use rbdc_dameng::common::sql::{
+        changes_schema, parse_insert, quote_identifier, quote_literal, with_scope_identity, InsertTarget,
+    };
+
+    fn target(schema: Option<&str>, table: &str, returning: bool) -> InsertTarget {
+        InsertTarget {
+            schema: schema.map(str::to_string),
+            table: table.to_string(),
+            returning,
+        }
+    }
+
+    #[test]
+    fn test_parse_insert_unquoted() {
+        assert_eq!(parse_insert("insert into t values (1)"), Some(target(None, "T", false)));
+        assert_eq!(parse_insert("  INSERT\n  INTO\tsysdba.t(id) values (1)"), Some(target(Some("SYSDBA"), "T", false)));
+        // 表名后直接跟括号
+        assert_eq!(parse_insert("insert into biz_activity(id,name) values (?,?)"), Some(target(None, "BIZ_ACTIVITY", false)));
+    }
+
+    #[test]
+    fn test_parse_insert_quoted() {
+        assert_eq!(
+            parse_insert(r#"insert into "Sales"."Order Item" ("id") values (?)"#),
+            Some(target(Some("Sales"), "Order Item", false))
+        );
+        assert_eq!(parse_insert(r#"insert into "a""b" values (1)"#), Some(target(None, "a\"b", false)));
+        assert_eq!(parse_insert(r#"insert into s."t" values (1)"#), Some(target(Some("S"), "t", false)));
+    }
+
+    #[test]
+    fn test_parse_insert_comments() {
+        assert_eq!(
+            parse_insert("/* batch */ insert /*+ APPEND */ into -- target\n t values (1)"),
+            Some(target(None, "T", false))
+        );
+    }
+
+    #[test]
+    fn test_parse_insert_returning() {
+        assert_eq!(
+            parse_insert("insert into t(name) values (?) returning id"),
+            Some(target(None, "T", true))
+        );
+        // 字符串常量里的 returning 不算
+        assert_eq!(
+            parse_insert("insert into t(name) values ('returning')"),
+            Some(target(None, "T", false))
+        );
+    }
+
+    #[test]
+    fn test_not_insert() {
+        assert_eq!(parse_insert("select * from t"), None);
+        assert_eq!(parse_insert("update t set a = 1"), None);
+        assert_eq!(parse_insert("insert"), None);
+        assert_eq!(parse_insert("insert into"), None);
+        assert_eq!(parse_insert("insert into (select 1)"), None);
+        assert_eq!(parse_insert(""), None);
+    }
+
+    #[test]
+    fn test_qualified_name() {
+        assert_eq!(target(Some("SYSDBA"), "T", false).qualified_name(), r#""SYSDBA"."T""#);
+        assert_eq!(target(None, "T", false).qualified_name(), r#""T""#);
+        assert_eq!(quote_literal("it's"), "'it''s'");
+        assert_eq!(quote_identifier(r#"a"b"#), r#""a""b""#);
+    }
+
+    #[test]
+    fn test_qualified_name_keeps_case() {
+        // 加引号的名字保持大小写，未加引号的名字已经转成大写，放进 IDENT_SEED 时都要带上双引号
+        let target = parse_insert(r#"INSERT INTO "biz"."MyTable"(name) VALUES (?)"#).unwrap();
+        assert_eq!(target.qualified_name(), r#""biz"."MyTable""#);
+        assert_eq!(quote_literal(&target.qualified_name()), r#"'"biz"."MyTable"'"#);
+
+        let target = parse_insert("insert into biz.my_table values (?)").unwrap();
+        assert_eq!(quote_literal(&target.qualified_name()), r#"'"BIZ"."MY_TABLE"'"#);
+
+        // 名字中的单引号和双引号都要转义
+        let target = parse_insert(r#"insert into "it's"."a""b" values (1)"#).unwrap();
+        assert_eq!(quote_literal(&target.qualified_name()), r#"'"it''s"."a""b"'"#);
+    }
+
+    #[test]
+    fn test_with_scope_identity() {
+        assert_eq!(
+            with_scope_identity("insert into t values (?)"),
+            "insert into t values (?); SELECT SCOPE_IDENTITY()"
+        );
+        assert_eq!(
+            with_scope_identity("insert into t values (?) ; ;  "),
+            "insert into t values (?); SELECT SCOPE_IDENTITY()"
+        );
+    }
+
+    #[test]
+    fn test_changes_schema() {
+        for sql in [
+            "create table t (id int)",
+            "  Alter TABLE t add c int",
+            "-- cleanup\ndrop table t",
+            "truncate table t",
+            "comment on table t is 'x'",
+            "set schema \"sales\"",
+        ] {
+            assert!(changes_schema(sql), "{}", sql);
+        }
+        for sql in [
+            "select * from t",
+            "insert into t values ('drop table t')",
+            "set transaction read only",
+            "\"drop\"",
+            "",
+        ] {
+            assert!(!changes_schema(sql), "{}", sql);
+        }
+    }
+}
@@ -0,0 +1,132 @@
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use rbdc_dameng::statement_cache::{CacheStats, StatementCache, WithOwner};
+
+    type Log = Rc<RefCell<Vec<&'static str>>>;
+
+    /// 模拟的连接，释放时记录下来
+    struct FakeConn(Log);
+
+    impl Drop for FakeConn {
+        fn drop(&mut self) {
+            self.0.borrow_mut().push("conn");
+        }
+    }
+
+    /// 模拟借用连接的语句，自己持有日志，连接释放后也能记录
+    struct FakeStmt {
+        _conn: &'static FakeConn,
+        log: Log,
+    }
+
+    impl Drop for FakeStmt {
+        fn drop(&mut self) {
+            self.log.borrow_mut().push("stmt");
+        }
+    }
+
+    #[test]
+    fn test_hit_and_miss() {
+        let mut cache = StatementCache::new(10);
+        assert_eq!(cache.take("select 1"), None);
+        cache.put("select 1", "stmt 1");
+        // 取出后缓存中不再有，放回之前同一条 SQL 需要重新准备
+        assert_eq!(cache.take("select 1"), Some("stmt 1"));
+        assert_eq!(cache.take("select 1"), None);
+        cache.put("select 1", "stmt 1");
+        assert_eq!(cache.take("select 2"), None);
+        assert_eq!(
+            cache.stats(),
+            CacheStats {
+                hits: 1,
+                misses: 3,
+                len: 1,
+                capacity: 10,
+            }
+        );
+    }
+
+    #[test]
+    fn test_evict_least_recently_used() {
+        let mut cache = StatementCache::new(2);
+        cache.put("a", 1);
+        cache.put("b", 2);
+        // 用过的 a 重新放回后变成最近使用的
+        let a = cache.take("a").unwrap();
+        cache.put("a", a);
+        cache.put("c", 3);
+        assert_eq!(cache.take("b"), None);
+        assert_eq!(cache.take("a"), Some(1));
+        assert_eq!(cache.take("c"), Some(3));
+    }
+
+    #[test]
+    fn test_evicted_statement_dropped() {
+        let stmt = Rc::new(());
+        let mut cache = StatementCache::new(1);
+        cache.put("a", stmt.clone());
+        cache.put("b", Rc::new(()));
+        assert_eq!(Rc::strong_count(&stmt), 1);
+    }
+
+    #[test]
+    fn test_put_same_sql_replaces() {
+        let mut cache = StatementCache::new(2);
+        cache.put("a", 1);
+        cache.put("a", 2);
+        assert_eq!(cache.stats().len, 1);
+        assert_eq!(cache.take("a"), Some(2));
+    }
+
+    #[test]
+    fn test_clear() {
+        let stmt = Rc::new(());
+        let mut cache = StatementCache::new(10);
+        cache.put("a", stmt.clone());
+        assert_eq!(cache.take("a").as_ref().map(Rc::strong_count), Some(2));
+        cache.put("a", stmt.clone());
+        cache.clear();
+        assert_eq!(Rc::strong_count(&stmt), 1);
+        assert_eq!(cache.take("a"), None);
+        // 清空不影响统计
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.len), (1, 1, 0));
+    }
+
+    #[test]
+    fn test_disabled() {
+        let mut cache = StatementCache::new(0);
+        cache.put("a", 1);
+        assert_eq!(cache.take("a"), None);
+        assert_eq!(
+            cache.stats(),
+            CacheStats {
+                hits: 0,
+                misses: 1,
+                len: 0,
+                capacity: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_owner_dropped_after_cached_statements() {
+        let log = Log::default();
+        let conn = Rc::new(FakeConn(log.clone()));
+        let mut cache = StatementCache::new(10);
+        for sql in ["select 1", "select 2"] {
+            let log = log.clone();
+            let stmt = unsafe { WithOwner::new(&conn, |c| Ok::<_, ()>(FakeStmt { _conn: c, log })) }.unwrap();
+            cache.put(sql, stmt);
+        }
+        // 会话先释放自己的连接，缓存中的语句仍然持有它
+        drop(conn);
+        assert!(log.borrow().is_empty());
+        // 每条语句都在它持有的连接之前释放，最后一条语句释放后连接才释放
+        drop(cache);
+        assert_eq!(*log.borrow(), ["stmt", "stmt", "conn"]);
+    }
+}
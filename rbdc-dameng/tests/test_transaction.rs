@@ -0,0 +1,295 @@
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+
+    use rbdc::Error;
+    use rbdc_dameng::options::{IsolationLevel, TxOptions};
+    use rbdc_dameng::transaction::{savepoint_name, Transaction, TxCommand, TxConnection, TxState};
+
+    const BROKEN: &str = "[SQLSTATE 08S01, DM -70019] 网络通信异常";
+    const CONSTRAINT: &str = "[SQLSTATE 23000, DM -6602] 违反表[T]唯一性约束";
+
+    /// 记录发出的调用，`fail` 中的调用返回对应的错误
+    #[derive(Default)]
+    struct FakeConn {
+        calls: RefCell<Vec<String>>,
+        fail: Vec<(&'static str, &'static str)>,
+    }
+
+    impl FakeConn {
+        fn failing(call: &'static str, error: &'static str) -> Self {
+            Self { fail: vec![(call, error)], ..Default::default() }
+        }
+
+        fn call(&self, call: String) -> Result<(), Error> {
+            let error = self.fail.iter().find(|(c, _)| *c == call).map(|(_, e)| Error::from(*e));
+            self.calls.borrow_mut().push(call);
+            error.map_or(Ok(()), Err)
+        }
+
+        fn take(&self) -> Vec<String> {
+            self.calls.take()
+        }
+    }
+
+    impl TxConnection for FakeConn {
+        fn set_autocommit(&self, enabled: bool) -> Result<(), Error> {
+            self.call(format!("AUTOCOMMIT {}", if enabled { "ON" } else { "OFF" }))
+        }
+
+        fn commit(&self) -> Result<(), Error> {
+            self.call("COMMIT".to_string())
+        }
+
+        fn rollback(&self) -> Result<(), Error> {
+            self.call("ROLLBACK".to_string())
+        }
+
+        fn execute(&self, sql: &str) -> Result<(), Error> {
+            self.call(sql.to_string())
+        }
+    }
+
+    fn begin_nested(tx: &mut Transaction, conn: &FakeConn, depth: usize) {
+        while tx.depth() < depth {
+            tx.execute(conn, TxCommand::Begin).unwrap();
+        }
+        conn.take();
+    }
+
+    #[test]
+    fn test_parse_tx_command() {
+        // rbatis 发出的语句
+        assert_eq!(TxCommand::parse("begin"), Some(TxCommand::Begin));
+        assert_eq!(TxCommand::parse("commit"), Some(TxCommand::Commit));
+        assert_eq!(TxCommand::parse("rollback"), Some(TxCommand::Rollback));
+
+        assert_eq!(TxCommand::parse("BEGIN TRANSACTION;"), Some(TxCommand::Begin));
+        assert_eq!(TxCommand::parse("start transaction"), Some(TxCommand::Begin));
+        assert_eq!(TxCommand::parse("  Commit Work "), Some(TxCommand::Commit));
+        assert_eq!(TxCommand::parse("rollback;"), Some(TxCommand::Rollback));
+    }
+
+    #[test]
+    fn test_parse_not_tx_command() {
+        assert_eq!(TxCommand::parse(""), None);
+        assert_eq!(TxCommand::parse("start"), None);
+        assert_eq!(TxCommand::parse("select 1"), None);
+        assert_eq!(TxCommand::parse("begin insert into t values (1); end;"), None);
+        assert_eq!(TxCommand::parse("rollback to savepoint sp_1"), None);
+        assert_eq!(TxCommand::parse("commit work now"), None);
+    }
+
+    #[test]
+    fn test_default_state() {
+        assert_eq!(TxState::default(), TxState::Idle);
+
+        let tx = Transaction::new(TxOptions::default());
+        assert_eq!(tx.state(), TxState::Idle);
+        assert_eq!(tx.depth(), 0);
+    }
+
+    #[test]
+    fn test_savepoint_name() {
+        assert_eq!(savepoint_name(1), "sp_1");
+        assert_eq!(savepoint_name(3), "sp_3");
+    }
+
+    #[test]
+    fn test_begin_commit() {
+        let conn = FakeConn::default();
+        let mut tx = Transaction::new(TxOptions::new().isolation_level(IsolationLevel::Serializable));
+        tx.execute(&conn, TxCommand::Begin).unwrap();
+        assert_eq!(tx.state(), TxState::Active);
+        tx.execute(&conn, TxCommand::Commit).unwrap();
+        assert_eq!(tx.depth(), 0);
+        assert_eq!(
+            conn.take(),
+            ["AUTOCOMMIT OFF", "SET TRANSACTION ISOLATION LEVEL SERIALIZABLE", "COMMIT", "AUTOCOMMIT ON"]
+        );
+
+        // 没有事务时的提交和回滚什么也不做
+        tx.execute(&conn, TxCommand::Commit).unwrap();
+        tx.execute(&conn, TxCommand::Rollback).unwrap();
+        assert!(conn.take().is_empty());
+    }
+
+    #[test]
+    fn test_begin_isolation_level_and_read_only() {
+        // SET TRANSACTION 只能是事务的第一条语句，两个参数合并成一条
+        let conn = FakeConn::default();
+        let options = TxOptions::new().isolation_level(IsolationLevel::Serializable).read_only(true);
+        let mut tx = Transaction::new(TxOptions::default());
+        tx.begin(&conn, &options).unwrap();
+        assert_eq!(tx.depth(), 1);
+        assert_eq!(conn.take(), ["AUTOCOMMIT OFF", "SET TRANSACTION ISOLATION LEVEL SERIALIZABLE READ ONLY"]);
+
+        // 嵌套事务沿用外层的设置
+        tx.begin(&conn, &options).unwrap();
+        assert_eq!(conn.take(), ["SAVEPOINT sp_1"]);
+    }
+
+    #[test]
+    fn test_nested_savepoints() {
+        let conn = FakeConn::default();
+        let mut tx = Transaction::new(TxOptions::default());
+        for _ in 0..3 {
+            tx.execute(&conn, TxCommand::Begin).unwrap();
+        }
+        assert_eq!(tx.depth(), 3);
+        assert_eq!(conn.take(), ["AUTOCOMMIT OFF", "SAVEPOINT sp_1", "SAVEPOINT sp_2"]);
+
+        tx.execute(&conn, TxCommand::Rollback).unwrap();
+        assert_eq!(tx.depth(), 2);
+        tx.execute(&conn, TxCommand::Commit).unwrap();
+        assert_eq!(tx.depth(), 1);
+        tx.execute(&conn, TxCommand::Rollback).unwrap();
+        assert_eq!(tx.depth(), 0);
+        assert_eq!(
+            conn.take(),
+            ["ROLLBACK TO SAVEPOINT sp_2", "RELEASE SAVEPOINT sp_1", "ROLLBACK", "AUTOCOMMIT ON"]
+        );
+    }
+
+    #[test]
+    fn test_rollback_all() {
+        let conn = FakeConn::default();
+        let mut tx = Transaction::new(TxOptions::default());
+        begin_nested(&mut tx, &conn, 3);
+        tx.rollback_all(&conn).unwrap();
+        assert_eq!(tx.depth(), 0);
+        assert_eq!(conn.take(), ["ROLLBACK", "AUTOCOMMIT ON"]);
+
+        tx.rollback_all(&conn).unwrap();
+        assert!(conn.take().is_empty());
+    }
+
+    #[test]
+    fn test_begin_failed() {
+        // SET TRANSACTION 失败时回滚并恢复自动提交，不进入事务
+        let conn = FakeConn::failing("SET TRANSACTION READ ONLY", CONSTRAINT);
+        let mut tx = Transaction::new(TxOptions::new().read_only(true));
+        let e = tx.execute(&conn, TxCommand::Begin).unwrap_err();
+        assert!(e.to_string().starts_with("SET TRANSACTION READ ONLY failed"), "{}", e);
+        assert_eq!(tx.depth(), 0);
+        assert_eq!(conn.take(), ["AUTOCOMMIT OFF", "SET TRANSACTION READ ONLY", "ROLLBACK", "AUTOCOMMIT ON"]);
+    }
+
+    #[test]
+    fn test_commit_failed() {
+        // 事务仍然有效，调用方可以回滚
+        let conn = FakeConn::failing("COMMIT", CONSTRAINT);
+        let mut tx = Transaction::new(TxOptions::default());
+        begin_nested(&mut tx, &conn, 1);
+        let e = tx.execute(&conn, TxCommand::Commit).unwrap_err();
+        assert!(e.to_string().starts_with("commit failed"), "{}", e);
+        assert_eq!(tx.depth(), 1);
+        assert_eq!(conn.take(), ["COMMIT"]);
+
+        tx.execute(&conn, TxCommand::Rollback).unwrap();
+        assert_eq!(tx.depth(), 0);
+        assert_eq!(conn.take(), ["ROLLBACK", "AUTOCOMMIT ON"]);
+    }
+
+    #[test]
+    fn test_restore_autocommit_failed_after_commit() {
+        // 事务已经提交，恢复自动提交失败不报告为提交失败
+        let conn = FakeConn::failing("AUTOCOMMIT ON", CONSTRAINT);
+        let mut tx = Transaction::new(TxOptions::default());
+        begin_nested(&mut tx, &conn, 1);
+        tx.execute(&conn, TxCommand::Commit).unwrap();
+        assert_eq!(tx.depth(), 0);
+        assert_eq!(conn.take(), ["COMMIT", "AUTOCOMMIT ON"]);
+
+        // 执行下一条语句之前重试，成功后不再重复
+        let e = tx.ensure_autocommit(&conn).unwrap_err();
+        assert!(e.to_string().starts_with("restore autocommit failed"), "{}", e);
+        let conn = FakeConn::default();
+        tx.ensure_autocommit(&conn).unwrap();
+        tx.ensure_autocommit(&conn).unwrap();
+        assert_eq!(conn.take(), ["AUTOCOMMIT ON"]);
+    }
+
+    #[test]
+    fn test_begin_after_restore_autocommit_failed() {
+        let conn = FakeConn::failing("AUTOCOMMIT ON", CONSTRAINT);
+        let mut tx = Transaction::new(TxOptions::default());
+        begin_nested(&mut tx, &conn, 1);
+        tx.execute(&conn, TxCommand::Rollback).unwrap();
+        assert_eq!(tx.depth(), 0);
+
+        // 新事务本来就要关闭自动提交，之后不再需要恢复
+        let conn = FakeConn::default();
+        tx.execute(&conn, TxCommand::Begin).unwrap();
+        tx.ensure_autocommit(&conn).unwrap();
+        assert_eq!(conn.take(), ["AUTOCOMMIT OFF"]);
+    }
+
+    #[test]
+    fn test_commit_connection_broken() {
+        // 服务端已经回滚了事务，返回错误并清零嵌套层数
+        let conn = FakeConn::failing("COMMIT", BROKEN);
+        let mut tx = Transaction::new(TxOptions::default());
+        begin_nested(&mut tx, &conn, 1);
+        assert!(tx.execute(&conn, TxCommand::Commit).is_err());
+        assert_eq!(tx.depth(), 0);
+        assert_eq!(conn.take(), ["COMMIT", "AUTOCOMMIT ON"]);
+    }
+
+    #[test]
+    fn test_rollback_connection_broken() {
+        // 连接断开时回滚视为成功
+        let conn = FakeConn::failing("ROLLBACK", BROKEN);
+        let mut tx = Transaction::new(TxOptions::default());
+        begin_nested(&mut tx, &conn, 1);
+        tx.execute(&conn, TxCommand::Rollback).unwrap();
+        assert_eq!(tx.depth(), 0);
+
+        let conn = FakeConn::failing("ROLLBACK", CONSTRAINT);
+        begin_nested(&mut tx, &conn, 1);
+        assert!(tx.execute(&conn, TxCommand::Rollback).is_err());
+        assert_eq!(tx.depth(), 1);
+    }
+
+    #[test]
+    fn test_nested_rollback_connection_broken() {
+        let conn = FakeConn::failing("ROLLBACK TO SAVEPOINT sp_2", BROKEN);
+        let mut tx = Transaction::new(TxOptions::default());
+        begin_nested(&mut tx, &conn, 3);
+        // 整个事务已经丢失，外层也无需再回滚
+        tx.execute(&conn, TxCommand::Rollback).unwrap();
+        assert_eq!(tx.depth(), 0);
+        assert_eq!(conn.take(), ["ROLLBACK TO SAVEPOINT sp_2", "AUTOCOMMIT ON"]);
+
+        let conn = FakeConn::failing("ROLLBACK TO SAVEPOINT sp_1", CONSTRAINT);
+        begin_nested(&mut tx, &conn, 2);
+        assert!(tx.execute(&conn, TxCommand::Rollback).is_err());
+        assert_eq!(tx.depth(), 2);
+    }
+
+    #[test]
+    fn test_nested_commit_failed() {
+        // 释放保存点失败不影响提交
+        let conn = FakeConn::failing("RELEASE SAVEPOINT sp_1", CONSTRAINT);
+        let mut tx = Transaction::new(TxOptions::default());
+        begin_nested(&mut tx, &conn, 2);
+        tx.execute(&conn, TxCommand::Commit).unwrap();
+        assert_eq!(tx.depth(), 1);
+
+        let conn = FakeConn::failing("RELEASE SAVEPOINT sp_1", BROKEN);
+        begin_nested(&mut tx, &conn, 2);
+        assert!(tx.execute(&conn, TxCommand::Commit).is_err());
+        assert_eq!(tx.depth(), 0);
+        assert_eq!(conn.take(), ["RELEASE SAVEPOINT sp_1", "AUTOCOMMIT ON"]);
+    }
+
+    #[test]
+    fn test_savepoint_connection_broken() {
+        let conn = FakeConn::failing("SAVEPOINT sp_1", BROKEN);
+        let mut tx = Transaction::new(TxOptions::default());
+        begin_nested(&mut tx, &conn, 1);
+        assert!(tx.execute(&conn, TxCommand::Begin).is_err());
+        assert_eq!(tx.depth(), 0);
+        assert_eq!(conn.take(), ["SAVEPOINT sp_1", "AUTOCOMMIT ON"]);
+    }
+}
@@ -0,0 +1,178 @@
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    use rbdc::Error;
+    use rbdc_dameng::cancel::{CancelOnDrop, CancelToken};
+    use rbdc_dameng::connection::DamengConnection;
+    use rbdc_dameng::worker::Worker;
+    use tokio::sync::mpsc;
+
+    /// 模拟的连接状态，记录执行过的操作，释放时设置标记
+    struct FakeSession {
+        log: Vec<String>,
+        dropped: Arc<AtomicBool>,
+    }
+
+    impl Drop for FakeSession {
+        fn drop(&mut self) {
+            self.dropped.store(true, Ordering::SeqCst);
+        }
+    }
+
+    async fn spawn_fake(name: &str) -> (Worker<FakeSession>, Arc<AtomicBool>) {
+        let dropped = Arc::new(AtomicBool::new(false));
+        let worker = Worker::spawn(name.to_string(), {
+            let dropped = dropped.clone();
+            move || {
+                Ok(FakeSession {
+                    log: vec![],
+                    dropped,
+                })
+            }
+        })
+        .await
+        .unwrap();
+        (worker, dropped)
+    }
+
+    /// 等待工作线程退出并释放状态
+    fn wait_dropped(dropped: &AtomicBool) {
+        for _ in 0..100 {
+            if dropped.load(Ordering::SeqCst) {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        panic!("worker state was not dropped");
+    }
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn test_send_sync() {
+        assert_send_sync::<DamengConnection>();
+        assert_send_sync::<Worker<FakeSession>>();
+    }
+
+    #[tokio::test]
+    async fn test_run_on_worker_thread() {
+        let (worker, _) = spawn_fake("fake-worker").await;
+        let name = worker
+            .run(|_| Ok(thread::current().name().map(str::to_string)))
+            .await
+            .unwrap();
+        assert_eq!(name.as_deref(), Some("fake-worker"));
+    }
+
+    #[tokio::test]
+    async fn test_run_in_order() {
+        let (worker, _) = spawn_fake("fake-worker").await;
+        // 先全部放进队列再等待，执行顺序与提交顺序相同
+        let pending: Vec<_> = (0..10)
+            .map(|i| {
+                worker.run(move |s: &mut FakeSession| {
+                    s.log.push(i.to_string());
+                    Ok(i)
+                })
+            })
+            .collect();
+        for (i, result) in pending.into_iter().enumerate() {
+            assert_eq!(result.await.unwrap(), i);
+        }
+        let log = worker.run(|s| Ok(s.log.join(","))).await.unwrap();
+        assert_eq!(log, "0,1,2,3,4,5,6,7,8,9");
+    }
+
+    #[tokio::test]
+    async fn test_dropped_queued_begin_not_run() {
+        let (worker, _) = spawn_fake("fake-worker").await;
+        // 先占住工作线程，后面的操作只能排队
+        let (release, blocked) = std::sync::mpsc::channel::<()>();
+        let busy = worker.run(move |s: &mut FakeSession| {
+            blocked.recv().unwrap();
+            s.log.push("busy".to_string());
+            Ok(())
+        });
+
+        // 与 exec("begin") 相同：事务控制语句执行前检查取消标记
+        let token = CancelToken::new();
+        let guard = CancelOnDrop(token.clone());
+        let begin = worker.run({
+            let token = token.clone();
+            move |s: &mut FakeSession| {
+                token.check_cancelled()?;
+                s.log.push("begin".to_string());
+                Ok(())
+            }
+        });
+        let begin = async move {
+            let _guard = guard;
+            begin.await
+        };
+        drop(begin);
+        assert!(token.is_cancelled());
+
+        release.send(()).unwrap();
+        busy.await.unwrap();
+        let log = worker.run(|s| Ok(s.log.join(","))).await.unwrap();
+        assert_eq!(log, "busy");
+    }
+
+    #[tokio::test]
+    async fn test_error_returned() {
+        let (worker, _) = spawn_fake("fake-worker").await;
+        let result: Result<(), Error> = worker.run(|_| Err(Error::from("boom"))).await;
+        assert_eq!(result.unwrap_err().to_string(), "boom");
+        // 出错后线程照常工作
+        assert!(worker.run(|_| Ok(())).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_init_failed() {
+        let result = Worker::<FakeSession>::spawn("fake-worker".to_string(), || Err(Error::from("connect failed"))).await;
+        assert_eq!(result.err().unwrap().to_string(), "connect failed");
+    }
+
+    #[tokio::test]
+    async fn test_panic_stops_worker() {
+        let (worker, dropped) = spawn_fake("fake-worker").await;
+        let result: Result<(), Error> = worker.run(|_| panic!("job panicked")).await;
+        assert!(result.is_err());
+        // 线程退出时状态被释放，之后的操作直接返回错误
+        wait_dropped(&dropped);
+        assert!(worker.run(|_| Ok(())).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_state_dropped_with_last_handle() {
+        let (worker, dropped) = spawn_fake("fake-worker").await;
+        worker.run(|_| Ok(())).await.unwrap();
+        assert!(!dropped.load(Ordering::SeqCst));
+        drop(worker);
+        wait_dropped(&dropped);
+    }
+
+    #[tokio::test]
+    async fn test_spawn_job_streams() {
+        let (worker, _) = spawn_fake("fake-worker").await;
+        let (tx, mut rx) = mpsc::channel(1);
+        worker
+            .spawn_job(move |_| {
+                for i in 0..3 {
+                    if tx.blocking_send(i).is_err() {
+                        break;
+                    }
+                }
+            })
+            .unwrap();
+        let mut received = vec![];
+        while let Some(i) = rx.recv().await {
+            received.push(i);
+        }
+        assert_eq!(received, vec![0, 1, 2]);
+    }
+}